@@ -0,0 +1,170 @@
+//! Line-by-line block structure of markdown source: enough to tell fenced
+//! and indented code from prose without parsing the whole document.
+
+/// What a source line is, as far as code is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    Blank,
+    /// Prose, headings, list items and everything else outside code
+    Text,
+    /// A fence opening or closing a code block
+    Fence,
+    /// A line inside a fenced code block, blank ones included
+    FencedCode,
+    /// A line of an indented code block
+    IndentedCode,
+}
+
+impl LineKind {
+    /// Whether the line belongs to a code block, fence lines included.
+    pub fn is_code(self) -> bool {
+        matches!(self, LineKind::Fence | LineKind::FencedCode | LineKind::IndentedCode)
+    }
+}
+
+/// Classifies source lines in order, tracking open fences, indented code
+/// and the list item a line may continue.
+#[derive(Debug)]
+pub struct Scanner {
+    /// The open fence's character and length
+    fence: Option<(char, usize)>,
+    /// The previous line was blank, or there was none
+    after_blank: bool,
+    in_indented_code: bool,
+    /// Column where the current list item's text starts
+    list_indent: Option<usize>,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Scanner {
+            fence: None,
+            after_blank: true,
+            in_indented_code: false,
+            list_indent: None,
+        }
+    }
+}
+
+impl Scanner {
+    pub fn new() -> Scanner {
+        Scanner::default()
+    }
+
+    /// Classify `line`, the next line of the document.
+    pub fn classify(&mut self, line: &str) -> LineKind {
+        let indent = indent_width(line);
+        let body = line.trim();
+
+        if let Some((ch, len)) = self.fence {
+            if fence_run(body).is_some_and(|(c, n, rest)| c == ch && n >= len && rest.is_empty()) {
+                self.fence = None;
+                self.after_blank = false;
+                return LineKind::Fence;
+            }
+            return LineKind::FencedCode;
+        }
+        if body.is_empty() {
+            self.after_blank = true;
+            return LineKind::Blank;
+        }
+
+        let base = self.list_indent.unwrap_or(0);
+        if indent >= base + 4 && (self.after_blank || self.in_indented_code) {
+            self.in_indented_code = true;
+            self.after_blank = false;
+            return LineKind::IndentedCode;
+        }
+        self.in_indented_code = false;
+        // Only a lazy continuation line may sit left of the item's text
+        if self.after_blank && indent < base {
+            self.list_indent = None;
+        }
+        self.after_blank = false;
+
+        let relative = indent.saturating_sub(self.list_indent.unwrap_or(0));
+        if relative < 4 {
+            if let Some((ch, len, info)) = fence_run(body) {
+                // A backtick fence's info string can't itself hold backticks
+                if !(ch == '`' && info.contains('`')) {
+                    self.fence = Some((ch, len));
+                    return LineKind::Fence;
+                }
+            }
+            if let Some(marker) = list_marker_width(body) {
+                self.list_indent = Some(indent + marker);
+            }
+        }
+        LineKind::Text
+    }
+}
+
+/// Columns of leading whitespace, with tabs stopping every 4 columns.
+fn indent_width(line: &str) -> usize {
+    let mut width = 0;
+    for ch in line.chars() {
+        match ch {
+            ' ' => width += 1,
+            '\t' => width += 4 - width % 4,
+            _ => break,
+        }
+    }
+    width
+}
+
+/// A run of three or more backticks or tildes starting `body`: the
+/// character, the run's length and the text after it.
+fn fence_run(body: &str) -> Option<(char, usize, &str)> {
+    let ch = body.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let rest = body.trim_start_matches(ch);
+    let len = body.len() - rest.len();
+    (len >= 3).then(|| (ch, len, rest.trim()))
+}
+
+/// Width of the list marker opening `body`, spaces after it included, if
+/// `body` starts a list item.
+fn list_marker_width(body: &str) -> Option<usize> {
+    let digits = body.len() - body.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let marker = match body[digits..].chars().next()? {
+        '-' | '*' | '+' if digits == 0 => 1,
+        '.' | ')' if (1..=9).contains(&digits) => digits + 1,
+        _ => return None,
+    };
+    let after = &body[marker..];
+    let spaces = after.len() - after.trim_start_matches(' ').len();
+    match spaces {
+        0 if !after.is_empty() => None,
+        // A marker alone on its line, or code right after it: text starts one past the marker
+        0 | 5.. => Some(marker + 1),
+        n => Some(marker + n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<LineKind> {
+        let mut scanner = Scanner::new();
+        input.lines().map(|line| scanner.classify(line)).collect()
+    }
+
+    #[test]
+    fn test_fences_close_on_matching_delimiter() {
+        use LineKind::*;
+        let input = "````\n~~~\n```\n````\ntext";
+        assert_eq!(kinds(input), [Fence, FencedCode, FencedCode, Fence, Text]);
+        let input = "~~~\n```\n~~~ not a close\n~~~\n\nafter";
+        assert_eq!(kinds(input), [Fence, FencedCode, FencedCode, Fence, Blank, Text]);
+    }
+
+    #[test]
+    fn test_indented_code_needs_a_blank_line() {
+        use LineKind::*;
+        let input = "para\n    lazy\n\n    code\n\n    more\nback";
+        assert_eq!(kinds(input), [Text, Text, Blank, IndentedCode, Blank, IndentedCode, Text]);
+        // In a list item, indentation is counted from the item's text
+        let input = "- item\n\n    continued\n\n      code";
+        assert_eq!(kinds(input), [Text, Blank, Text, Blank, IndentedCode]);
+    }
+}
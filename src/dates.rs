@@ -0,0 +1,142 @@
+//! Date placeholders: replace `{{date}}` and `{{now}}` in prose with the current time.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::blocks::Scanner;
+
+/// Format for `{{date}}` when no `--date-format` is given.
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+/// Format for `{{now}}` when no `--date-format` is given.
+pub const NOW_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Seconds since the Unix epoch, or 0 if the clock is set before it.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Year, month and day of the UTC date `days` after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's algorithm, counting from 0000-03-01 so leap days come last
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// Format `secs` since the epoch (in UTC) with strftime-style fields:
+/// `%Y`, `%y`, `%m`, `%d`, `%H`, `%M`, `%S` and `%%`. Other text, including
+/// unknown fields, is copied as is.
+pub fn format(secs: u64, format: &str) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let time = secs % 86_400;
+    let (hour, minute, second) = (time / 3600, time % 3600 / 60, time % 60);
+
+    let mut out = String::with_capacity(format.len() + 8);
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&year.to_string()),
+            Some('y') => out.push_str(&format!("{:02}", year.rem_euclid(100))),
+            Some('m') => out.push_str(&format!("{:02}", month)),
+            Some('d') => out.push_str(&format!("{:02}", day)),
+            Some('H') => out.push_str(&format!("{:02}", hour)),
+            Some('M') => out.push_str(&format!("{:02}", minute)),
+            Some('S') => out.push_str(&format!("{:02}", second)),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+/// Replace `{{date}}` and `{{now}}` in `content` with the time `secs`, using
+/// `date_format` for both if given. Fenced and indented code blocks and inline
+/// code spans are left alone, so documentation about the placeholders stays
+/// literal.
+pub fn substitute(content: &str, secs: u64, date_format: Option<&str>) -> String {
+    let date = format(secs, date_format.unwrap_or(DATE_FORMAT));
+    let now = format(secs, date_format.unwrap_or(NOW_FORMAT));
+    let replace = |text: &str| text.replace("{{date}}", &date).replace("{{now}}", &now);
+
+    let mut out = String::with_capacity(content.len());
+    let mut scanner = Scanner::new();
+    for line in content.split_inclusive('\n') {
+        if scanner.classify(line).is_code() || !line.contains("{{") {
+            out.push_str(line);
+        } else {
+            outside_code_spans(line, &mut out, replace);
+        }
+    }
+    out
+}
+
+/// Append `line` to `out`, passing the text outside backtick code spans
+/// through `replace`.
+fn outside_code_spans(line: &str, out: &mut String, replace: impl Fn(&str) -> String) {
+    let mut rest = line;
+    while let Some(open) = rest.find('`') {
+        let ticks = rest[open..].len() - rest[open..].trim_start_matches('`').len();
+        let fence = &rest[open..open + ticks];
+        let after = &rest[open + ticks..];
+        // A span closes at the next run of exactly as many backticks
+        let close = after.match_indices(fence).find(|&(i, _)| {
+            !after[i + ticks..].starts_with('`') && (i == 0 || !after[..i].ends_with('`'))
+        });
+        let Some((close, _)) = close else {
+            break;
+        };
+        out.push_str(&replace(&rest[..open]));
+        let end = open + ticks + close + ticks;
+        out.push_str(&rest[open..end]);
+        rest = &rest[end..];
+    }
+    out.push_str(&replace(rest));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-02-29 13:05:09 UTC
+    const LEAP_DAY: u64 = 1_709_211_909;
+
+    #[test]
+    fn test_format_fields() {
+        assert_eq!(format(0, "%Y-%m-%d %H:%M:%S"), "1970-01-01 00:00:00");
+        assert_eq!(format(LEAP_DAY, "%d/%m/%y %H%M%S"), "29/02/24 130509");
+        assert_eq!(format(LEAP_DAY, "100%% on %Y %q"), "100% on 2024 %q");
+    }
+
+    #[test]
+    fn test_substitute_skips_code() {
+        let input = "Report for {{date}}, built {{now}}.\n\nUse `{{date}}` here.\n\n```\n{{date}}\n```\n";
+        assert_eq!(
+            substitute(input, LEAP_DAY, None),
+            "Report for 2024-02-29, built 2024-02-29 13:05.\n\nUse `{{date}}` here.\n\n```\n{{date}}\n```\n"
+        );
+        assert_eq!(
+            substitute("{{date}} ``a`{{now}}`` {{now}}", LEAP_DAY, Some("%d.%m.%Y")),
+            "29.02.2024 ``a`{{now}}`` 29.02.2024"
+        );
+        // Indented code, and fences holding other fence markers, stay literal
+        let input = "Text:\n\n    {{date}}\n\n````\n~~~\n{{date}}\n````\n{{date}}\n";
+        assert_eq!(
+            substitute(input, LEAP_DAY, None),
+            "Text:\n\n    {{date}}\n\n````\n~~~\n{{date}}\n````\n2024-02-29\n"
+        );
+    }
+}
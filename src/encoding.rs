@@ -0,0 +1,120 @@
+//! Decode input in encodings other than UTF-8.
+
+use std::str::FromStr;
+
+/// Input encodings accepted by `--encoding`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1: every byte is the code point of the same value
+    Latin1,
+    /// Latin-1 with printable characters (curly quotes, €, …) in 0x80–0x9F
+    Windows1252,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Ok(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Ok(Encoding::Latin1),
+            "windows-1252" | "cp1252" => Ok(Encoding::Windows1252),
+            _ => Err(format!(
+                "unknown encoding '{}' (expected utf-8, utf-16le, utf-16be, latin1, windows-1252)",
+                s
+            )),
+        }
+    }
+}
+
+/// Characters for bytes 0x80–0x9F in Windows-1252; undefined bytes map to
+/// the C1 control of the same value, as browsers do.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// The encoding announced by a byte order mark at the start of `bytes`, and
+/// the mark's length.
+fn detect_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    match bytes {
+        [0xEF, 0xBB, 0xBF, ..] => Some((Encoding::Utf8, 3)),
+        [0xFF, 0xFE, ..] => Some((Encoding::Utf16Le, 2)),
+        [0xFE, 0xFF, ..] => Some((Encoding::Utf16Be, 2)),
+        _ => None,
+    }
+}
+
+/// Decode `bytes` to a string. A byte order mark takes precedence over
+/// `encoding`, which defaults to UTF-8. Invalid sequences become U+FFFD
+/// instead of failing, so a stray byte doesn't lose the whole document.
+pub fn decode(bytes: &[u8], encoding: Option<Encoding>) -> String {
+    let (encoding, bytes) = match detect_bom(bytes) {
+        Some((bom, len)) => (bom, &bytes[len..]),
+        None => (encoding.unwrap_or_default(), bytes),
+    };
+    match encoding {
+        Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+        Encoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+        Encoding::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+        Encoding::Latin1 => bytes.iter().map(|&b| char::from(b)).collect(),
+        Encoding::Windows1252 => bytes
+            .iter()
+            .map(|&b| match b {
+                0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                _ => char::from(b),
+            })
+            .collect(),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks(2).map(|pair| match pair {
+        [a, b] => unit([*a, *b]),
+        // A dangling odd byte can't be a valid code unit
+        _ => 0xFFFD,
+    });
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_latin1() {
+        let bytes = b"caf\xe9 na\xefve";
+        assert_eq!(decode(bytes, Some(Encoding::Latin1)), "café naïve");
+        // Read as UTF-8, the same bytes are replaced rather than rejected
+        assert_eq!(decode(bytes, None), "caf\u{FFFD} na\u{FFFD}ve");
+    }
+
+    #[test]
+    fn test_decode_windows1252_quotes() {
+        assert_eq!(decode(b"\x93hi\x94 \x80", Some(Encoding::Windows1252)), "“hi” €");
+    }
+
+    #[test]
+    fn test_bom_overrides_encoding() {
+        let utf16le = [0xFF, 0xFE, b'#', 0, b' ', 0, 0xE9, 0];
+        assert_eq!(decode(&utf16le, Some(Encoding::Latin1)), "# é");
+        let utf16be = [0xFE, 0xFF, 0, b'a', 0xD8];
+        assert_eq!(decode(&utf16be, None), "a\u{FFFD}");
+        assert_eq!(decode(b"\xEF\xBB\xBFhi", Some(Encoding::Latin1)), "hi");
+    }
+
+    #[test]
+    fn test_encoding_names() {
+        assert_eq!("ISO-8859-1".parse(), Ok(Encoding::Latin1));
+        assert_eq!("utf_16le".parse(), Ok(Encoding::Utf16Le));
+        assert!("ebcdic".parse::<Encoding>().is_err());
+    }
+}
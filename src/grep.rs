@@ -0,0 +1,150 @@
+//! Grep mode: keep only the blocks whose text matches a pattern.
+//!
+//! Works on the parsed event stream. Each top-level block (or each item of a
+//! top-level list) is tested as a unit, and the headings it sits under are
+//! kept for orientation.
+
+use pulldown_cmark::{Event, HeadingLevel, Tag};
+
+/// A heading above the current position and whether it's been output yet.
+struct Heading<'a> {
+    level: HeadingLevel,
+    events: Vec<Event<'a>>,
+    shown: bool,
+}
+
+/// Keep the blocks of `events` whose text contains `pattern`, ignoring case,
+/// preceded by the headings they belong to. Lists are filtered item by item.
+pub fn filter<'a>(events: Vec<Event<'a>>, pattern: &str) -> Vec<Event<'a>> {
+    let pattern = pattern.to_lowercase();
+    let mut out = Vec::new();
+    let mut headings: Vec<Heading<'a>> = Vec::new();
+    let mut events = events.into_iter();
+
+    while let Some(mut block) = next_block(&mut events) {
+        match block.first() {
+            Some(Event::Start(Tag::Heading { level, .. })) => {
+                let level = *level;
+                headings.retain(|h| h.level < level);
+                let found = matches(&block, &pattern);
+                headings.push(Heading {
+                    level,
+                    events: block,
+                    shown: false,
+                });
+                if found {
+                    show_headings(&mut headings, &mut out);
+                }
+            }
+            Some(Event::Start(Tag::List(_))) if block.len() >= 2 => {
+                let close = block.pop().unwrap();
+                let mut inner = block.drain(1..);
+                let mut items = Vec::new();
+                while let Some(item) = next_block(&mut inner) {
+                    if matches(&item, &pattern) {
+                        items.extend(item);
+                    }
+                }
+                drop(inner);
+                if !items.is_empty() {
+                    show_headings(&mut headings, &mut out);
+                    out.extend(block);
+                    out.extend(items);
+                    out.push(close);
+                }
+            }
+            _ => {
+                if matches(&block, &pattern) {
+                    show_headings(&mut headings, &mut out);
+                    out.extend(block);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Take the next top-level block: a start tag through its matching end, or
+/// a single event outside any tag.
+fn next_block<'a>(events: &mut impl Iterator<Item = Event<'a>>) -> Option<Vec<Event<'a>>> {
+    let mut block = Vec::new();
+    let mut depth = 0usize;
+    for event in events {
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        block.push(event);
+        if depth == 0 {
+            return Some(block);
+        }
+    }
+    (!block.is_empty()).then_some(block)
+}
+
+/// Whether the plain text of `block` contains the lowercased `pattern`.
+fn matches(block: &[Event<'_>], pattern: &str) -> bool {
+    let mut text = String::new();
+    for event in block {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(t),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    text.to_lowercase().contains(pattern)
+}
+
+/// Output the headings above the current block that aren't shown yet.
+fn show_headings<'a>(headings: &mut [Heading<'a>], out: &mut Vec<Event<'a>>) {
+    for heading in headings.iter_mut().filter(|h| !h.shown) {
+        out.extend(heading.events.iter().cloned());
+        heading.shown = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser;
+    use crate::renderer::{render, RenderOptions};
+
+    fn grep(input: &str, pattern: &str) -> Vec<String> {
+        let opts = RenderOptions {
+            use_color: false,
+            ..RenderOptions::default()
+        };
+        render(filter(parser::parse(input), pattern), 80, &opts)
+            .into_iter()
+            .filter(|l| !l.trim().is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn test_only_matching_paragraphs_with_parent_heading() {
+        let input = "# Guide\n\nIntro text.\n\n## Setup\n\nInstall the TOOL first.\n\n\
+                     Unrelated paragraph.\n\n## Usage\n\nNothing here.";
+        let lines = grep(input, "tool");
+        assert_eq!(lines.len(), 3, "Got: {:?}", lines);
+        assert!(lines[0].contains("Guide"));
+        assert!(lines[1].contains("Setup"));
+        assert!(lines[2].contains("Install the TOOL first."));
+    }
+
+    #[test]
+    fn test_list_filtered_by_item() {
+        let input = "- apples\n- bananas\n- green apples\n\nNo fruit here.";
+        let lines = grep(input, "Apple");
+        assert_eq!(lines.len(), 2, "Got: {:?}", lines);
+        assert!(lines[0].contains("apples") && lines[1].contains("green apples"));
+    }
+
+    #[test]
+    fn test_matching_heading_kept_without_body() {
+        let input = "# Top\n\n## Install\n\nSteps.\n\n## Other\n\nText.";
+        let lines = grep(input, "install");
+        assert_eq!(lines.len(), 2, "Got: {:?}", lines);
+        assert!(lines[1].contains("Install"));
+    }
+}
@@ -0,0 +1,547 @@
+//! Lightweight line-based syntax highlighting for fenced code blocks.
+//!
+//! Each language is described by its keywords, line-comment marker and string
+//! quotes; lines are tokenized independently, so multi-line strings and block
+//! comments are not tracked. Diffs are colored by each line's `+`/`-` prefix,
+//! and data formats (JSON, TOML, YAML) color keys apart from their values.
+
+use crate::style;
+
+/// A language the highlighter knows about.
+pub struct Language {
+    /// Canonical name, as shown by `--list-languages`.
+    pub name: &'static str,
+    /// Other fence tags that select this language.
+    pub aliases: &'static [&'static str],
+    keywords: &'static [&'static str],
+    line_comment: Option<&'static str>,
+    quotes: &'static [char],
+    case_insensitive: bool,
+    kind: Kind,
+}
+
+/// How a language's lines are highlighted.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Keywords, strings, numbers and comments
+    Code,
+    /// Unified diff: whole lines colored by their prefix
+    Diff,
+    /// Key/value data: keys, strings, and numbers or literals in three colors
+    Data,
+}
+
+const C_LIKE_QUOTES: &[char] = &['"', '\''];
+
+/// Registry of supported languages.
+pub const LANGUAGES: &[Language] = &[
+    Language {
+        name: "c",
+        aliases: &["h"],
+        keywords: &[
+            "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum",
+            "extern", "float", "for", "goto", "if", "int", "long", "return", "short", "signed",
+            "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned", "void",
+            "volatile", "while", "NULL",
+        ],
+        line_comment: Some("//"),
+        quotes: C_LIKE_QUOTES,
+        case_insensitive: false,
+        kind: Kind::Code,
+    },
+    Language {
+        name: "cpp",
+        aliases: &["c++", "cc", "hpp"],
+        keywords: &[
+            "auto", "bool", "break", "case", "catch", "char", "class", "const", "constexpr",
+            "continue", "default", "delete", "do", "double", "else", "enum", "false", "float",
+            "for", "if", "int", "long", "namespace", "new", "nullptr", "private", "protected",
+            "public", "return", "static", "struct", "switch", "template", "this", "throw", "true",
+            "try", "typename", "using", "virtual", "void", "while",
+        ],
+        line_comment: Some("//"),
+        quotes: C_LIKE_QUOTES,
+        case_insensitive: false,
+        kind: Kind::Code,
+    },
+    Language {
+        name: "diff",
+        aliases: &["patch"],
+        keywords: &[],
+        line_comment: None,
+        quotes: &[],
+        case_insensitive: false,
+        kind: Kind::Diff,
+    },
+    Language {
+        name: "go",
+        aliases: &["golang"],
+        keywords: &[
+            "break", "case", "chan", "const", "continue", "default", "defer", "else", "false",
+            "for", "func", "go", "if", "import", "interface", "map", "nil", "package", "range",
+            "return", "select", "struct", "switch", "true", "type", "var",
+        ],
+        line_comment: Some("//"),
+        quotes: &['"', '\'', '`'],
+        case_insensitive: false,
+        kind: Kind::Code,
+    },
+    Language {
+        name: "java",
+        aliases: &[],
+        keywords: &[
+            "abstract", "boolean", "break", "case", "catch", "class", "else", "extends", "false",
+            "final", "for", "if", "implements", "import", "int", "interface", "new", "null",
+            "package", "private", "protected", "public", "return", "static", "super", "switch",
+            "this", "throw", "throws", "true", "try", "void", "while",
+        ],
+        line_comment: Some("//"),
+        quotes: C_LIKE_QUOTES,
+        case_insensitive: false,
+        kind: Kind::Code,
+    },
+    Language {
+        name: "javascript",
+        aliases: &["js", "jsx", "mjs"],
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "default",
+            "delete", "else", "export", "extends", "false", "for", "from", "function", "if",
+            "import", "in", "instanceof", "let", "new", "null", "of", "return", "switch", "this",
+            "throw", "true", "try", "typeof", "undefined", "var", "while", "yield",
+        ],
+        line_comment: Some("//"),
+        quotes: &['"', '\'', '`'],
+        case_insensitive: false,
+        kind: Kind::Code,
+    },
+    Language {
+        name: "json",
+        aliases: &["jsonc"],
+        keywords: &["true", "false", "null"],
+        line_comment: None,
+        quotes: &['"'],
+        case_insensitive: false,
+        kind: Kind::Data,
+    },
+    Language {
+        name: "python",
+        aliases: &["py", "python3"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "False", "finally", "for", "from", "if", "import", "in",
+            "is", "lambda", "None", "not", "or", "pass", "raise", "return", "True", "try",
+            "while", "with", "yield",
+        ],
+        line_comment: Some("#"),
+        quotes: C_LIKE_QUOTES,
+        case_insensitive: false,
+        kind: Kind::Code,
+    },
+    Language {
+        name: "rust",
+        aliases: &["rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+            "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super",
+            "trait", "true", "type", "unsafe", "use", "where", "while",
+        ],
+        line_comment: Some("//"),
+        quotes: &['"'],
+        case_insensitive: false,
+        kind: Kind::Code,
+    },
+    Language {
+        name: "sh",
+        aliases: &["bash", "shell", "zsh"],
+        keywords: &[
+            "case", "do", "done", "echo", "elif", "else", "esac", "exit", "export", "fi", "for",
+            "function", "if", "in", "local", "return", "then", "until", "while",
+        ],
+        line_comment: Some("#"),
+        quotes: C_LIKE_QUOTES,
+        case_insensitive: false,
+        kind: Kind::Code,
+    },
+    Language {
+        name: "sql",
+        aliases: &[],
+        keywords: &[
+            "and", "as", "by", "create", "delete", "from", "group", "insert", "into", "join",
+            "left", "limit", "not", "null", "on", "or", "order", "select", "set", "table",
+            "update", "values", "where",
+        ],
+        line_comment: Some("--"),
+        quotes: C_LIKE_QUOTES,
+        case_insensitive: true,
+        kind: Kind::Code,
+    },
+    Language {
+        name: "toml",
+        aliases: &[],
+        keywords: &["true", "false"],
+        line_comment: Some("#"),
+        quotes: C_LIKE_QUOTES,
+        case_insensitive: false,
+        kind: Kind::Data,
+    },
+    Language {
+        name: "typescript",
+        aliases: &["ts", "tsx"],
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "else",
+            "enum", "export", "extends", "false", "for", "from", "function", "if", "implements",
+            "import", "interface", "let", "new", "null", "return", "switch", "this", "throw",
+            "true", "try", "type", "undefined", "var", "while",
+        ],
+        line_comment: Some("//"),
+        quotes: &['"', '\'', '`'],
+        case_insensitive: false,
+        kind: Kind::Code,
+    },
+    Language {
+        name: "yaml",
+        aliases: &["yml"],
+        keywords: &["true", "false", "null", "yes", "no"],
+        line_comment: Some("#"),
+        quotes: C_LIKE_QUOTES,
+        case_insensitive: false,
+        kind: Kind::Data,
+    },
+];
+
+const KEYWORD: &str = style::FG_MAGENTA;
+const STRING: &str = style::FG_GREEN;
+const NUMBER: &str = style::FG_YELLOW;
+const COMMENT: &str = style::DIM;
+const KEY: &str = style::FG_CYAN;
+const ADDED: &str = style::FG_GREEN;
+const REMOVED: &str = style::FG_RED;
+const HUNK: &str = style::FG_CYAN;
+/// Changed words within a modified line, inside its `ADDED`/`REMOVED` color
+const CHANGED: &str = style::REVERSE;
+
+/// Look up a language by fence tag (name or alias, case-insensitive).
+pub fn find(tag: &str) -> Option<&'static Language> {
+    let tag = tag.to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|l| l.name == tag || l.aliases.contains(&tag.as_str()))
+}
+
+impl Language {
+    fn is_keyword(&self, word: &str) -> bool {
+        if self.case_insensitive {
+            self.keywords.iter().any(|k| k.eq_ignore_ascii_case(word))
+        } else {
+            self.keywords.contains(&word)
+        }
+    }
+
+    /// Whether this is the `diff` language.
+    pub fn is_diff(&self) -> bool {
+        self.kind == Kind::Diff
+    }
+
+    /// Return `line` with ANSI color codes around keywords, strings, numbers and comments.
+    pub fn highlight_line(&self, line: &str) -> String {
+        if self.kind == Kind::Diff {
+            return highlight_diff_line(line, ADDED, REMOVED);
+        }
+        let mut out = String::with_capacity(line.len() * 2);
+        let mut rest = line;
+
+        while let Some(ch) = rest.chars().next() {
+            if self.line_comment.is_some_and(|c| rest.starts_with(c)) {
+                push_styled(&mut out, rest, COMMENT);
+                break;
+            }
+
+            let data = self.kind == Kind::Data;
+            let token_len = if self.quotes.contains(&ch) {
+                let len = string_len(rest, ch);
+                let key = data && key_follows(&rest[len..], true);
+                push_styled(&mut out, &rest[..len], if key { KEY } else { STRING });
+                len
+            } else if ch.is_ascii_digit() || (data && ch == '-' && starts_number(&rest[1..])) {
+                let sign = usize::from(ch == '-');
+                let len = rest[sign..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                    .map_or(rest.len(), |i| i + sign);
+                push_styled(&mut out, &rest[..len], NUMBER);
+                len
+            } else if ch.is_alphabetic() || ch == '_' {
+                // Data keys may contain dashes and dots, like `build-deps` or `a.b`
+                let len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || (data && "-.".contains(c))))
+                    .unwrap_or(rest.len());
+                let word = &rest[..len];
+                if data && key_follows(&rest[len..], false) {
+                    push_styled(&mut out, word, KEY);
+                } else if self.is_keyword(word) {
+                    push_styled(&mut out, word, if data { NUMBER } else { KEYWORD });
+                } else {
+                    out.push_str(word);
+                }
+                len
+            } else {
+                out.push(ch);
+                ch.len_utf8()
+            };
+
+            rest = &rest[token_len..];
+        }
+
+        out
+    }
+}
+
+/// Whether the text after a token marks it as a key: `=` (TOML) or `:`
+/// (JSON, YAML). After a bare word the colon must end the token, so a URL's
+/// `http:` isn't a key.
+fn key_follows(after: &str, quoted: bool) -> bool {
+    let after = after.trim_start();
+    if after.starts_with('=') {
+        return true;
+    }
+    match after.strip_prefix(':') {
+        Some(rest) => quoted || rest.chars().next().is_none_or(char::is_whitespace),
+        None => false,
+    }
+}
+
+fn starts_number(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Color a diff line by its prefix, with `added` and `removed` for `+` and
+/// `-` lines (themes can swap the default green and red).
+pub fn highlight_diff_line(line: &str, added: &str, removed: &str) -> String {
+    match diff_color(line, added, removed) {
+        Some(color) => format!("{}{}{}", color, line, style::RESET),
+        None => line.to_string(),
+    }
+}
+
+/// Color for a diff line, from its prefix.
+fn diff_color<'a>(line: &str, added: &'a str, removed: &'a str) -> Option<&'a str> {
+    if line.starts_with("+++") || line.starts_with("---") {
+        Some(style::BOLD)
+    } else if line.starts_with('+') {
+        Some(added)
+    } else if line.starts_with('-') {
+        Some(removed)
+    } else if line.starts_with("@@") {
+        Some(HUNK)
+    } else {
+        None
+    }
+}
+
+/// Highlight the lines of a diff, and within each removed line followed by
+/// an added one, also mark the words that changed between the two. A run of
+/// removed lines is paired line by line with the run of added lines after it.
+pub fn highlight_word_diff(lines: &[String], added_color: &str, removed_color: &str) -> Vec<String> {
+    let mut out: Vec<String> = lines
+        .iter()
+        .map(|l| highlight_diff_line(l, added_color, removed_color))
+        .collect();
+    let is_removed = |l: &String| l.starts_with('-') && !l.starts_with("---");
+    let is_added = |l: &String| l.starts_with('+') && !l.starts_with("+++");
+    let mut i = 0;
+    while i < lines.len() {
+        let removed = lines[i..].iter().take_while(|l| is_removed(l)).count();
+        let added = lines[i + removed..].iter().take_while(|l| is_added(l)).count();
+        for k in 0..removed.min(added) {
+            let (old, new) = (i + k, i + removed + k);
+            let (old_line, new_line) = word_diff_pair(&lines[old][1..], &lines[new][1..]);
+            out[old] = format!("{}-{}{}", removed_color, old_line, style::RESET);
+            out[new] = format!("{}+{}{}", added_color, new_line, style::RESET);
+        }
+        i += (removed + added).max(1);
+    }
+    out
+}
+
+/// Split a line into words, runs of whitespace and single punctuation marks.
+fn diff_tokens(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut prev = None;
+    for (i, c) in line.char_indices() {
+        let kind = class(c);
+        if i > start && (prev != Some(kind) || kind == 2) {
+            tokens.push(&line[start..i]);
+            start = i;
+        }
+        prev = Some(kind);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+/// Mark the tokens of `old` and `new` that aren't in their longest common
+/// subsequence.
+fn word_diff_pair(old: &str, new: &str) -> (String, String) {
+    let (a, b) = (diff_tokens(old), diff_tokens(new));
+    // lcs[i][j]: common tokens of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut in_a, mut in_b) = (vec![false; a.len()], vec![false; b.len()]);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            in_a[i] = true;
+            in_b[j] = true;
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    (mark_changed(&a, &in_a), mark_changed(&b, &in_b))
+}
+
+fn mark_changed(tokens: &[&str], common: &[bool]) -> String {
+    let mut out = String::new();
+    let mut changed = false;
+    for (token, &same) in tokens.iter().zip(common) {
+        if !same && !changed {
+            out.push_str(CHANGED);
+        } else if same && changed {
+            out.push_str(style::NO_REVERSE);
+        }
+        changed = !same;
+        out.push_str(token);
+    }
+    if changed {
+        out.push_str(style::NO_REVERSE);
+    }
+    out
+}
+
+/// Byte length of the string literal at the start of `s`, including quotes.
+/// An unterminated literal runs to the end of the line.
+fn string_len(s: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, ch) in s.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == quote {
+            return i + ch.len_utf8();
+        }
+    }
+    s.len()
+}
+
+fn push_styled(out: &mut String, text: &str, code: &str) {
+    out.push_str(code);
+    out.push_str(text);
+    out.push_str(style::RESET);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_by_name_and_alias() {
+        assert_eq!(find("rust").map(|l| l.name), Some("rust"));
+        assert_eq!(find("RS").map(|l| l.name), Some("rust"));
+        assert_eq!(find("yml").map(|l| l.name), Some("yaml"));
+        assert!(find("brainfuck").is_none());
+    }
+
+    #[test]
+    fn test_registry_has_core_languages() {
+        let names: Vec<&str> = LANGUAGES.iter().map(|l| l.name).collect();
+        for core in ["rust", "json", "python", "sh"] {
+            assert!(names.contains(&core), "missing {}", core);
+        }
+    }
+
+    #[test]
+    fn test_highlight_rust_line() {
+        let rust = find("rust").unwrap();
+        let out = rust.highlight_line("let s = \"hi\"; // note");
+        assert!(out.contains(&format!("{}let{}", KEYWORD, style::RESET)));
+        assert!(out.contains(&format!("{}\"hi\"{}", STRING, style::RESET)));
+        assert!(out.contains(&format!("{}// note{}", COMMENT, style::RESET)));
+        assert_eq!(style::visible_len(&out), "let s = \"hi\"; // note".len());
+    }
+
+    #[test]
+    fn test_comment_marker_inside_string_is_not_comment() {
+        let py = find("python").unwrap();
+        let out = py.highlight_line("x = '#not' # yes");
+        assert!(out.contains(&format!("{}'#not'{}", STRING, style::RESET)));
+        assert!(out.contains(&format!("{}# yes{}", COMMENT, style::RESET)));
+    }
+
+    #[test]
+    fn test_diff_lines_colored_by_prefix() {
+        let diff = find("patch").unwrap();
+        assert_eq!(diff.highlight_line("+new"), format!("{}+new{}", ADDED, style::RESET));
+        assert_eq!(diff.highlight_line("-old"), format!("{}-old{}", REMOVED, style::RESET));
+        assert_eq!(diff.highlight_line(" same"), " same");
+    }
+
+    #[test]
+    fn test_word_diff_marks_only_changed_word() {
+        let lines = [" context", "-the quick fox", "+the slow fox"].map(String::from);
+        let out = highlight_word_diff(&lines, ADDED, REMOVED);
+        assert_eq!(out[0], " context");
+        let changed = |w: &str| format!("{}{}{}", CHANGED, w, style::NO_REVERSE);
+        assert_eq!(out[1], format!("{}-the {} fox{}", REMOVED, changed("quick"), style::RESET));
+        assert_eq!(out[2], format!("{}+the {} fox{}", ADDED, changed("slow"), style::RESET));
+        // Unchanged words carry no highlight
+        assert!(!out[2].contains(&format!("{}the", CHANGED)));
+        assert!(!out[2].contains(&format!("{} fox", CHANGED)));
+    }
+
+    #[test]
+    fn test_json_keys_distinct_from_string_values() {
+        let json = find("json").unwrap();
+        let out = json.highlight_line(r#"  "name": "markterm", "size": -12, "ok": true"#);
+        assert!(out.contains(&format!("{}\"name\"{}", KEY, style::RESET)), "Got: {:?}", out);
+        assert!(out.contains(&format!("{}\"markterm\"{}", STRING, style::RESET)), "Got: {:?}", out);
+        assert!(out.contains(&format!("{}-12{}", NUMBER, style::RESET)), "Got: {:?}", out);
+        assert!(out.contains(&format!("{}true{}", NUMBER, style::RESET)), "Got: {:?}", out);
+        assert_ne!(KEY, STRING);
+    }
+
+    #[test]
+    fn test_yaml_and_toml_keys() {
+        let yaml = find("yaml").unwrap();
+        let out = yaml.highlight_line("build-deps: http://example.com # note");
+        assert!(out.starts_with(&format!("{}build-deps{}", KEY, style::RESET)), "Got: {:?}", out);
+        assert!(!out.contains(&format!("{}http", KEY)), "Got: {:?}", out);
+        let toml = find("toml").unwrap();
+        let out = toml.highlight_line("version = \"0.1\"");
+        assert!(out.starts_with(&format!("{}version{}", KEY, style::RESET)), "Got: {:?}", out);
+        assert!(out.contains(&format!("{}\"0.1\"{}", STRING, style::RESET)));
+    }
+}
@@ -0,0 +1,157 @@
+//! Transclusion: expand `{{include: file.md}}` / `<!-- include: file.md -->` lines.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::blocks::Scanner;
+
+/// Maximum nesting of include directives.
+pub const MAX_DEPTH: usize = 16;
+
+/// Expand include directives in `content`. Paths are resolved relative to the
+/// directory of `source` (or the current directory for stdin). Directives inside
+/// fenced or indented code blocks are left alone. Unreadable files, cycles, and excessive
+/// nesting are reported on stderr and the directive line is kept as-is.
+pub fn expand(content: &str, source: Option<&Path>) -> String {
+    let mut stack = Vec::new();
+    if let Some(path) = source.and_then(|p| fs::canonicalize(p).ok()) {
+        stack.push(path);
+    }
+    let base_dir = source
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    expand_inner(content, &base_dir, &mut stack)
+}
+
+fn expand_inner(content: &str, base_dir: &Path, stack: &mut Vec<PathBuf>) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut scanner = Scanner::new();
+
+    for line in content.split_inclusive('\n') {
+        let target = if scanner.classify(line).is_code() {
+            None
+        } else {
+            directive_target(line.trim())
+        };
+        match target.and_then(|t| load(base_dir, t, stack)) {
+            Some(mut included) => {
+                if !included.ends_with('\n') {
+                    included.push('\n');
+                }
+                out.push_str(&included);
+            }
+            None => out.push_str(line),
+        }
+    }
+
+    out
+}
+
+/// Read and recursively expand one included file, or `None` if it can't be used.
+fn load(base_dir: &Path, target: &str, stack: &mut Vec<PathBuf>) -> Option<String> {
+    let path = base_dir.join(target);
+    let canonical = match fs::canonicalize(&path) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("markterm: include {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    if stack.contains(&canonical) {
+        eprintln!("markterm: include {}: cycle detected, skipping", path.display());
+        return None;
+    }
+    if stack.len() >= MAX_DEPTH {
+        eprintln!("markterm: include {}: nesting deeper than {}, skipping", path.display(), MAX_DEPTH);
+        return None;
+    }
+    let content = match fs::read_to_string(&canonical) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("markterm: include {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    stack.push(canonical.clone());
+    let nested_base = canonical.parent().map(Path::to_path_buf).unwrap_or_default();
+    let expanded = expand_inner(&content, &nested_base, stack);
+    stack.pop();
+    Some(expanded)
+}
+
+/// Extract the file name from an include directive line, if it is one.
+fn directive_target(line: &str) -> Option<&str> {
+    let inner = line
+        .strip_prefix("{{")
+        .and_then(|l| l.strip_suffix("}}"))
+        .or_else(|| line.strip_prefix("<!--").and_then(|l| l.strip_suffix("-->")))?;
+    let target = inner.trim().strip_prefix("include:")?.trim();
+    (!target.is_empty()).then_some(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("markterm-include-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_directive_target() {
+        assert_eq!(directive_target("{{include: a.md}}"), Some("a.md"));
+        assert_eq!(directive_target("<!-- include: dir/b.md -->"), Some("dir/b.md"));
+        assert_eq!(directive_target("{{date}}"), None);
+        assert_eq!(directive_target("<!-- a comment -->"), None);
+    }
+
+    #[test]
+    fn test_include_pulls_in_heading() {
+        let dir = temp_dir("basic");
+        fs::write(dir.join("chapter1.md"), "# Chapter One\n\nBody.\n").unwrap();
+        let main = dir.join("book.md");
+        let content = "# Book\n\n{{include: chapter1.md}}\n\nThe end.\n";
+        fs::write(&main, content).unwrap();
+
+        let expanded = expand(content, Some(&main));
+        assert!(expanded.contains("# Chapter One"), "Got: {}", expanded);
+        assert!(!expanded.contains("{{include"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_cycle_is_skipped() {
+        let dir = temp_dir("cycle");
+        fs::write(dir.join("a.md"), "A\n<!-- include: b.md -->\n").unwrap();
+        fs::write(dir.join("b.md"), "B\n<!-- include: a.md -->\n").unwrap();
+        let a = dir.join("a.md");
+
+        let expanded = expand(&fs::read_to_string(&a).unwrap(), Some(&a));
+        assert_eq!(expanded, "A\nB\n<!-- include: a.md -->\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_ignored_in_code_fence() {
+        let content = "```\n{{include: missing.md}}\n```\n";
+        assert_eq!(expand(content, None), content);
+    }
+
+    #[test]
+    fn test_include_ignored_in_nested_fence_and_indented_code() {
+        let dir = temp_dir("code");
+        fs::write(dir.join("x.md"), "spliced\n").unwrap();
+        let main = dir.join("main.md");
+        // A `~~~` line inside a ```` fence doesn't close it
+        let content = "````\n~~~\n{{include: x.md}}\n````\n\nText.\n\n    {{include: x.md}}\n";
+        assert_eq!(expand(content, Some(&main)), content);
+        // After the fence closes, directives work again
+        let content = "````\n~~~\n````\n{{include: x.md}}\n";
+        assert_eq!(expand(content, Some(&main)), "````\n~~~\n````\nspliced\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
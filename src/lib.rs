@@ -0,0 +1,356 @@
+//! markterm: render markdown to ANSI-styled terminal lines.
+//!
+//! The binary wires these modules together; embedders can use the renderer
+//! directly without the interactive pager.
+//!
+//! Rendering is a pure function of the input, the width and the
+//! [`RenderOptions`](renderer::RenderOptions): it never queries the terminal,
+//! reads environment variables or looks at the clock. That is left to the
+//! binary, so [`render_snapshot`] output is stable enough to compare against
+//! stored snapshots.
+
+pub mod blocks;
+pub mod dates;
+pub mod encoding;
+pub mod grep;
+pub mod highlight;
+pub mod include;
+pub mod log;
+pub mod math;
+pub mod pager;
+pub mod parser;
+pub mod progress;
+pub mod renderer;
+pub mod style;
+pub mod terminal;
+pub mod watch;
+
+use renderer::{RenderOptions, Rendered};
+use style::Theme;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Render `input` as a single screen of exactly `height` lines, as the pager
+/// would show its first page. Short documents are padded with blank lines,
+/// long ones are cut off.
+pub fn render_screen(input: &str, width: u16, height: u16, opts: &RenderOptions) -> Vec<String> {
+    let lines = renderer::render(parser::parse(input), width, opts);
+    let height = height as usize;
+    let range = pager::visible_range(lines.len(), 0, height);
+    let mut screen: Vec<String> = lines[range].to_vec();
+    screen.resize(height, String::new());
+    screen
+}
+
+/// Render `input` as one ready-to-print string: lines joined with `\n`,
+/// ending in a newline.
+///
+/// ```
+/// use markterm::renderer::RenderOptions;
+///
+/// let out = markterm::render_to_string("# Hello\n\nWorld.", 40, &RenderOptions::default());
+/// assert!(out.contains("Hello"));
+/// assert!(out.ends_with('\n'));
+/// ```
+pub fn render_to_string(input: &str, width: u16, opts: &RenderOptions) -> String {
+    let mut out = renderer::render(parser::parse(input), width, opts).join("\n");
+    out.push('\n');
+    out
+}
+
+/// Render `input` for a snapshot test: default options at a fixed `width`,
+/// colored with `theme`, or plain text without any escape codes for `None`.
+/// The output depends only on the arguments and this crate's version.
+///
+/// For plain snapshots of colored output, [`style::strip_ansi`] removes the
+/// escape codes and leaves the text and layout:
+///
+/// ```
+/// use markterm::style::{self, Theme};
+///
+/// let colored = markterm::render_snapshot("**Hi**", 40, Some(Theme::default()));
+/// assert_eq!(style::strip_ansi(&colored), markterm::render_snapshot("**Hi**", 40, None));
+/// ```
+pub fn render_snapshot(input: &str, width: u16, theme: Option<Theme>) -> String {
+    let opts = RenderOptions {
+        use_color: theme.is_some(),
+        theme: theme.unwrap_or_default(),
+        ..RenderOptions::default()
+    };
+    render_to_string(input, width, &opts)
+}
+
+/// Number of lines `input` occupies when rendered at `width` columns.
+pub fn rendered_height(input: &str, width: u16, opts: &RenderOptions) -> usize {
+    renderer::render(parser::parse(input), width, opts).len()
+}
+
+/// The heading structure of `input` as an indented tree, one heading per
+/// line with its level and source line. A heading is nested under the closest
+/// preceding heading of a lower level, so skipped levels don't add indentation.
+pub fn outline(input: &str) -> Vec<String> {
+    let mut parents: Vec<usize> = Vec::new();
+    parser::headings(input)
+        .into_iter()
+        .map(|heading| {
+            while parents.last().is_some_and(|&level| level >= heading.level) {
+                parents.pop();
+            }
+            let indent = "  ".repeat(parents.len());
+            parents.push(heading.level);
+            format!("{}H{} {} (line {})", indent, heading.level, heading.text, heading.line)
+        })
+        .collect()
+}
+
+/// The part of `input` from byte `offset` on, moved back to the start of the
+/// block containing it: the closest unindented line before `offset` that
+/// follows a blank line outside a code block. Used to render only what was
+/// appended to a growing file without cutting a paragraph, code block or
+/// list item in half; a list can still be split between its items.
+pub fn tail_from(input: &str, offset: usize) -> &str {
+    if offset >= input.len() {
+        return "";
+    }
+    let mut scanner = blocks::Scanner::new();
+    let mut start = 0;
+    let mut line_start = 0;
+    let mut after_blank = true;
+    // Whether `offset` falls on a blank line, so the next block is the start
+    let mut offset_blank = false;
+    for line in input.split_inclusive('\n') {
+        let kind = scanner.classify(line);
+        let blank = kind == blocks::LineKind::Blank;
+        // Indented lines continue a list item or are code, so they can't start a block
+        let block_start = after_blank
+            && !line.starts_with([' ', '\t'])
+            && matches!(kind, blocks::LineKind::Text | blocks::LineKind::Fence);
+        if line_start > offset && !blank {
+            if block_start && offset_blank {
+                start = line_start;
+            }
+            break;
+        }
+        if block_start {
+            start = line_start;
+        }
+        if line_start + line.len() > offset && line_start <= offset {
+            offset_blank = blank;
+        }
+        after_blank = blank;
+        line_start += line.len();
+    }
+    &input[start..]
+}
+
+/// Parse and render `input`, turning a panic anywhere in the parser or
+/// renderer into an error carrying the panic message.
+pub fn try_render(input: &str, width: u16, opts: &RenderOptions) -> Result<Rendered, String> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut events = 0;
+        let doc = renderer::render_stream(parser::parse_iter(input).inspect(|_| events += 1), width, opts);
+        info!("parsed {} events", events);
+        info!("rendered {} lines at width {}", doc.lines.len(), width);
+        doc
+    }))
+    .map_err(|payload| {
+        payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string())
+    })
+}
+
+/// The document as plain text, one rendered line per input line. Used when
+/// rendering fails so the user still sees the content.
+pub fn render_raw(input: &str) -> Rendered {
+    Rendered {
+        // Drop control characters so stray escapes can't garble the terminal
+        lines: input
+            .lines()
+            .map(|l| l.chars().filter(|c| !c.is_control() || *c == '\t').collect())
+            .collect(),
+        links: Vec::new(),
+        code_blocks: Vec::new(),
+        footnotes: Vec::new(),
+        squeezed_tables: Vec::new(),
+    }
+}
+
+/// The markdown source itself, one line per input line, for reading it in
+/// the pager. With color, headings are bold and code fences dim.
+pub fn render_source(input: &str, use_color: bool) -> Rendered {
+    let mut doc = render_raw(input);
+    if use_color {
+        let mut in_fence = false;
+        for line in &mut doc.lines {
+            let trimmed = line.trim_start();
+            let fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+            in_fence ^= fence;
+            let code = if fence {
+                style::DIM
+            } else if !in_fence && trimmed.starts_with('#') {
+                style::BOLD
+            } else {
+                continue;
+            };
+            *line = style::styled(line, &[code], true);
+        }
+    }
+    doc
+}
+
+/// Already-styled terminal output, one line per input line, for paging
+/// without rendering. Escape sequences pass through untouched.
+pub fn render_passthrough(input: &str) -> Rendered {
+    Rendered {
+        lines: input.lines().map(String::from).collect(),
+        links: Vec::new(),
+        code_blocks: Vec::new(),
+        footnotes: Vec::new(),
+        squeezed_tables: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_source_styles_headings_outside_fences() {
+        let input = "# Title\n\n```sh\n# comment\n```\n*text*";
+        let doc = render_source(input, true);
+        assert_eq!(doc.lines[0], style::styled("# Title", &[style::BOLD], true));
+        assert_eq!(doc.lines[2], style::styled("```sh", &[style::DIM], true));
+        assert_eq!(doc.lines[3], "# comment");
+        assert_eq!(doc.lines[5], "*text*");
+        assert_eq!(render_source(input, false).lines, input.lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_passthrough_keeps_lines_unchanged() {
+        let input = "\x1b[1mbold\x1b[0m line\n  # not a heading  \n\n*not italic*\n";
+        let doc = render_passthrough(input);
+        assert_eq!(
+            doc.lines,
+            ["\x1b[1mbold\x1b[0m line", "  # not a heading  ", "", "*not italic*"]
+        );
+    }
+
+    #[test]
+    fn test_outline_nests_headings() {
+        let input = "# Guide\n\nIntro.\n\n## Install\n\n### From source\n\n## Usage\n";
+        assert_eq!(
+            outline(input),
+            [
+                "H1 Guide (line 1)",
+                "  H2 Install (line 5)",
+                "    H3 From source (line 7)",
+                "  H2 Usage (line 9)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_screen_pads_short_input() {
+        let screen = render_screen("# Title\n\nHello.", 40, 10, &RenderOptions::default());
+        assert_eq!(screen.len(), 10);
+        assert!(screen.iter().any(|l| l.contains("Hello.")));
+    }
+
+    #[test]
+    fn test_render_screen_truncates_long_input() {
+        let input: String = (0..50).map(|i| format!("Paragraph {}\n\n", i)).collect();
+        let screen = render_screen(&input, 40, 8, &RenderOptions::default());
+        assert_eq!(screen.len(), 8);
+        assert!(!screen.iter().any(|l| l.contains("Paragraph 49")));
+    }
+
+    #[test]
+    fn test_rendered_height_grows_as_width_shrinks() {
+        let input = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod \
+                     tempor incididunt ut labore et dolore magna aliqua.";
+        let opts = RenderOptions::default();
+        let wide = rendered_height(input, 120, &opts);
+        let medium = rendered_height(input, 60, &opts);
+        let narrow = rendered_height(input, 30, &opts);
+        assert!(wide < medium && medium < narrow, "{} {} {}", wide, medium, narrow);
+    }
+
+    /// Deterministic xorshift generator so failures are reproducible.
+    fn next_random(seed: &mut u64) -> u64 {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 7;
+        *seed ^= *seed << 17;
+        *seed
+    }
+
+    #[test]
+    fn test_try_render_survives_random_input() {
+        // Bias towards markdown syntax so the parser hits interesting paths
+        const ALPHABET: &[u8] = b"#*_~`>-+|[]()!:\\<>\n\n  \t1.xa\xc3\xa9\xff\x00\x1b";
+        let mut seed = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..500 {
+            let len = (next_random(&mut seed) % 200) as usize;
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| ALPHABET[(next_random(&mut seed) % ALPHABET.len() as u64) as usize])
+                .collect();
+            let input = String::from_utf8_lossy(&bytes);
+            for width in [4, 20, 80] {
+                assert!(try_render(&input, width, &RenderOptions::default()).is_ok(), "{:?}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_render_survives_deep_nesting() {
+        for unit in [">", "- ", "[", "*", "1. "] {
+            let input = unit.repeat(5000) + "text";
+            assert!(try_render(&input, 40, &RenderOptions::default()).is_ok(), "{}", unit);
+        }
+    }
+
+    #[test]
+    fn test_render_raw_keeps_text_and_strips_escapes() {
+        let doc = render_raw("# Title\n\x1b[31mred\x1b[0m\tcell");
+        assert_eq!(doc.lines, vec!["# Title", "[31mred[0m\tcell"]);
+        assert!(doc.links.is_empty());
+    }
+
+    #[test]
+    fn test_tail_from_starts_at_block_boundary() {
+        let input = "# Log\n\nFirst entry\nwraps here.\n\n```\ncode\n\nmore code\n```\n\nLast.\n";
+        // Mid-paragraph: back to the paragraph's first line
+        let mid = input.find("wraps").unwrap();
+        assert_eq!(tail_from(input, mid), &input[input.find("First").unwrap()..]);
+        // A blank line inside a fence is not a boundary
+        let in_code = input.find("more code").unwrap();
+        assert_eq!(tail_from(input, in_code), &input[input.find("```").unwrap()..]);
+        // Already at a block start
+        let last = input.find("Last").unwrap();
+        assert_eq!(tail_from(input, last), "Last.\n");
+        // On the blank line before a block: that block
+        assert_eq!(tail_from(input, last - 1), "Last.\n");
+        assert_eq!(tail_from(input, 0), input);
+        assert_eq!(tail_from(input, input.len() + 10), "");
+
+        // The continuation of a loose list item stays with the item
+        let input = "Intro.\n\n- first item\n\n  more of it\n\n- second\n";
+        let mid = input.find("more").unwrap();
+        assert_eq!(tail_from(input, mid), &input[input.find("- first").unwrap()..]);
+        // A shorter or different fence inside a fence doesn't close it
+        let input = "````\n~~~\n\n```\n\nstill code\n````\n";
+        let in_code = input.find("still").unwrap();
+        assert_eq!(tail_from(input, in_code), input);
+    }
+
+    #[test]
+    fn test_render_snapshot_is_deterministic() {
+        let input = "# Title\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n```rust\nlet x = 1;\n```\n\n- [ ] task [link](http://x)\n";
+        for theme in [None, Some(Theme::default()), Theme::named("dark256")] {
+            let first = render_snapshot(input, 40, theme.clone());
+            assert_eq!(first.as_bytes(), render_snapshot(input, 40, theme).as_bytes());
+        }
+        assert!(!render_snapshot(input, 40, None).contains('\x1b'));
+    }
+}
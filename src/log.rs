@@ -0,0 +1,56 @@
+//! Minimal leveled logging to stderr, enabled with `-v` / `-vv`.
+//!
+//! Messages never go to stdout, so piped rendered output is unaffected.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Processing stages: input size, event and line counts, terminal setup.
+pub const INFO: u8 = 1;
+/// Per-keystroke pager events.
+pub const DEBUG: u8 = 2;
+
+static LEVEL: AtomicU8 = AtomicU8::new(0);
+
+/// Set the verbosity; messages at or below `level` are printed.
+pub fn set_level(level: u8) {
+    LEVEL.store(level, Ordering::Relaxed);
+}
+
+/// Whether messages at `level` are currently printed.
+pub fn enabled(level: u8) -> bool {
+    level != 0 && level <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Log a stage of processing (shown with `-v`).
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::INFO) {
+            eprintln!("markterm: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+/// Log fine-grained detail (shown with `-vv`).
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::DEBUG) {
+            eprintln!("markterm: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        set_level(1);
+        assert!(enabled(INFO));
+        assert!(!enabled(DEBUG));
+        set_level(0);
+        assert!(!enabled(INFO));
+    }
+}
@@ -1,90 +1,580 @@
-mod pager;
-mod parser;
-mod renderer;
-#[allow(dead_code)]
-mod style;
-mod terminal;
-
 use clap::Parser;
+use markterm::renderer::{RenderOptions, Rendered};
+use markterm::style::Theme;
+use markterm::{dates, encoding, highlight, include, info, pager, progress, renderer, terminal, watch};
 use std::fs;
 use std::io::{self, IsTerminal, Read};
+use std::panic;
+use std::path::Path;
 use std::process;
 
+/// Exit status when the input is empty or has no renderable content.
+const EXIT_EMPTY: i32 = 2;
+
+/// Exit status when `--check` finds lines wider than the target width.
+const EXIT_CHECK: i32 = 3;
+
 #[derive(Parser)]
 #[command(name = "markterm", version, about = "Render markdown in the terminal with built-in paging")]
 struct Cli {
     /// Markdown file to display (use - for stdin)
-    file: String,
+    #[arg(required_unless_present_any = ["list_languages", "eval", "explain_width"])]
+    file: Option<String>,
 
-    /// Override terminal width
+    /// Render this markdown string instead of a file
+    #[arg(short, long, value_name = "MARKDOWN", conflicts_with = "file")]
+    eval: Option<String>,
+
+    /// Override terminal width (also read from MARKTERM_WIDTH)
     #[arg(short, long)]
     width: Option<u16>,
 
-    /// Color theme: auto, dark, light, none
-    #[arg(short, long, default_value = "auto")]
+    /// Color theme: auto, dark, light, dark256, light256, colorblind, none
+    #[arg(short, long, default_value = "auto", value_parser = clap::builder::PossibleValuesParser::new(Theme::NAMES))]
     theme: String,
 
     /// Dump rendered output without paging
     #[arg(long)]
     no_pager: bool,
 
+    /// Print only the first N rendered lines (implies --no-pager)
+    #[arg(long, value_name = "N")]
+    head: Option<usize>,
+
+    /// Plain text hard-wrapped at N columns, code blocks included (implies --no-pager)
+    #[arg(long, value_name = "N", conflicts_with = "no_wrap")]
+    fold: Option<u16>,
+
+    /// Lay the document out in N side-by-side columns
+    #[arg(long, value_name = "N", conflicts_with = "fold")]
+    columns: Option<usize>,
+
     /// Truncate long lines with ellipsis instead of wrapping
     #[arg(long)]
     no_wrap: bool,
+
+    /// Where to cut long lines in --no-wrap mode: start, middle, end
+    #[arg(long, default_value = "end", value_name = "POS")]
+    truncate: renderer::TruncatePosition,
+
+    /// Inline code style: bg, brackets (fixed ‹› delimiters, [] with --ascii), underline (for
+    /// terminals without background colors)
+    #[arg(long, default_value = "bg", value_name = "STYLE")]
+    inline_code_style: renderer::InlineCodeStyle,
+
+    /// Table border style: grid, simple, minimal
+    #[arg(long, default_value = "grid")]
+    table_style: renderer::TableStyle,
+
+    /// Heading style: color, underline (H1/H2 followed by a full-width rule)
+    #[arg(long, default_value = "color", value_name = "STYLE")]
+    heading_style: renderer::HeadingStyle,
+
+    /// Ordered list numbering: decimal, alpha, roman, legal (1., 1.1., 1.1.1.)
+    #[arg(long, default_value = "decimal", value_name = "STYLE")]
+    ordered_style: renderer::OrderedStyle,
+
+    /// Bullets for unordered list levels, comma-separated (default: •,◦,▪)
+    #[arg(long, value_name = "LIST", value_delimiter = ',')]
+    bullets: Vec<String>,
+
+    /// In diff code blocks, highlight the changed words of modified lines
+    #[arg(long)]
+    word_diff: bool,
+
+    /// Show a "3/5 tasks complete" line after each list with task items
+    #[arg(long)]
+    task_summary: bool,
+
+    /// Italicize the first paragraph after the title as a lead
+    #[arg(long)]
+    lead_style: bool,
+
+    /// Append word count, reading time and heading count
+    #[arg(long)]
+    stats: bool,
+
+    /// Inline image protocol: auto, kitty, iterm, sixel, none
+    #[arg(long, default_value = "auto", value_name = "PROTO",
+          value_parser = ["auto", "kitty", "iterm", "sixel", "none"])]
+    image_protocol: String,
+
+    /// Repeat table headers after every N data rows
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    table_header_repeat: usize,
+
+    /// Show <!-- comments --> as dim italic notes
+    #[arg(long)]
+    show_comments: bool,
+
+    /// Show a one-line paragraph directly before a table as its caption
+    #[arg(long)]
+    table_captions: bool,
+
+    /// Number of blank lines between blocks
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    block_spacing: usize,
+
+    /// Show links as numbered references listed at the end
+    #[arg(long)]
+    link_refs: bool,
+
+    /// Indent all but the first line of each paragraph by N columns
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    hanging_indent: usize,
+
+    /// Wrap prose at N columns, letting code blocks and tables use the full width
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    prose_width: usize,
+
+    /// Cut each paragraph off after N wrapped lines, ending with an ellipsis
+    #[arg(long, default_value_t = 0, value_name = "N")]
+    max_paragraph_lines: usize,
+
+    /// Tab stop width for tabs in prose
+    #[arg(long, default_value_t = 4, value_name = "N")]
+    tab_width: usize,
+
+    /// Use only ASCII characters for borders, bullets and symbols
+    #[arg(long)]
+    ascii: bool,
+
+    /// Plain 7-bit ASCII with no escape codes, for printers, logs and braille displays
+    #[arg(long)]
+    teletype: bool,
+
+    /// Show ->, =>, <=, >=, != and similar in prose as Unicode symbols
+    #[arg(long)]
+    symbols: bool,
+
+    /// Without color, show emphasis as *bold*, _italic_, ~~strike~~
+    #[arg(long)]
+    keep_markers: bool,
+
+    /// Render code fences with this language verbatim (repeatable)
+    #[arg(long, value_name = "LANG")]
+    raw_fence: Vec<String>,
+
+    /// Show only blocks containing PATTERN (case-insensitive), under their headings
+    #[arg(long, value_name = "PATTERN")]
+    grep: Option<String>,
+
+    /// Line up `**Term** — definition` list items as a two-column glossary
+    #[arg(long)]
+    glossary: bool,
+
+    /// Leave tables out of the output
+    #[arg(long)]
+    no_tables: bool,
+
+    /// Leave code blocks out of the output
+    #[arg(long)]
+    no_code: bool,
+
+    /// Leave images out of the output
+    #[arg(long)]
+    no_images: bool,
+
+    /// Expand `{{include: FILE}}` and `<!-- include: FILE -->` directives
+    #[arg(long)]
+    include: bool,
+
+    /// Replace {{date}} and {{now}} in prose with the current UTC date and time
+    #[arg(long)]
+    dates: bool,
+
+    /// strftime-style format for {{date}} and {{now}}, e.g. "%d.%m.%Y" (implies --dates)
+    #[arg(long, value_name = "FMT")]
+    date_format: Option<String>,
+
+    /// Page the input as already-styled terminal output, without rendering markdown
+    #[arg(long, conflicts_with_all = ["watch", "fold", "columns"])]
+    pager_only: bool,
+
+    /// Page the markdown source itself instead of rendering it
+    #[arg(long, conflicts_with_all = ["pager_only", "watch", "fold", "columns"])]
+    raw: bool,
+
+    /// In the pager, Enter opens the selected link with the OS default handler
+    /// (http and https URLs only)
+    #[arg(long)]
+    open_links: bool,
+
+    /// Pager rows past the end of the document: tilde, blank
+    #[arg(long, default_value = "tilde", value_name = "STYLE")]
+    empty_lines: pager::EmptyLines,
+
+    /// Draw a scrollbar in the pager's rightmost column (narrows the text by one)
+    #[arg(long)]
+    scrollbar: bool,
+
+    /// Render only from byte N on, starting at the beginning of the block
+    /// that contains it (for tailing a growing file)
+    #[arg(long, value_name = "N")]
+    since_byte: Option<usize>,
+
+    /// Input encoding: utf-8, utf-16le, utf-16be, latin1, windows-1252
+    /// (a byte order mark overrides it; invalid bytes are replaced)
+    #[arg(long, value_name = "NAME")]
+    encoding: Option<encoding::Encoding>,
+
+    /// Re-render whenever the file changes (pager only)
+    #[arg(long)]
+    watch: bool,
+
+    /// List code block languages with syntax highlighting and exit
+    #[arg(long)]
+    list_languages: bool,
+
+    /// Print only the heading structure as an indented tree, with source lines
+    #[arg(long)]
+    outline: bool,
+
+    /// Print the rendering width and where it came from, then exit
+    #[arg(long)]
+    explain_width: bool,
+
+    /// Fail on rendering errors instead of falling back to the raw text
+    #[arg(long)]
+    strict: bool,
+
+    /// Keep trailing spaces on printed lines (they are trimmed by default)
+    #[arg(long)]
+    keep_trailing: bool,
+
+    /// Print only the code blocks, exactly as written in the source
+    #[arg(long)]
+    code_plain_copy: bool,
+
+    /// Report rendered lines wider than the width instead of printing the document
+    #[arg(long)]
+    check: bool,
+
+    /// Log processing stages to stderr (-vv also logs pager keys)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Suppress the message printed for empty documents (exit status is still 2)
+    #[arg(short, long)]
+    quiet: bool,
 }
 
 fn main() {
     let cli = Cli::parse();
+    markterm::log::set_level(cli.verbose);
+
+    if cli.list_languages {
+        for lang in highlight::LANGUAGES {
+            if lang.aliases.is_empty() {
+                println!("{}", lang.name);
+            } else {
+                println!("{} ({})", lang.name, lang.aliases.join(", "));
+            }
+        }
+        return;
+    }
+
+    // Determine terminal dimensions
+    let term_size = terminal::query_size();
+    let (term_width, term_height) = term_size.unwrap_or(terminal::DEFAULT_SIZE);
+    info!("terminal size {}x{}", term_width, term_height);
+    let env_width = std::env::var("MARKTERM_WIDTH").ok();
+    let (width, width_source) = match cli.fold {
+        Some(cols) => (cols, terminal::WidthSource::Flag),
+        None => terminal::resolve_width_source(
+            cli.width,
+            env_width.as_deref(),
+            term_size.map(|(w, _)| w),
+        ),
+    };
+    info!("width {} from {}", width, width_source);
+
+    if cli.explain_width {
+        println!("{} ({})", width, width_source);
+        return;
+    }
+
+    // clap requires FILE unless listing languages or given --eval
+    let file = cli.file.as_deref().unwrap_or("-");
 
     // Read input
-    let content = if cli.file == "-" {
-        let mut buf = String::new();
-        io::stdin().read_to_string(&mut buf).unwrap_or_else(|e| {
-            eprintln!("markterm: error reading stdin: {}", e);
-            process::exit(1);
-        });
-        buf
+    let content = if let Some(markdown) = &cli.eval {
+        markdown.clone()
     } else {
-        fs::read_to_string(&cli.file).unwrap_or_else(|e| {
-            eprintln!("markterm: {}: {}", cli.file, e);
+        // A spinner on stderr if reading is slow, gone before anything else is printed
+        let source = if file == "-" { "stdin" } else { file };
+        let spinner = progress::Spinner::start(&format!("reading {}", source));
+        let read = if file == "-" {
+            let mut buf = Vec::new();
+            io::stdin().read_to_end(&mut buf).map(|_| buf)
+        } else {
+            fs::read(file)
+        };
+        spinner.finish();
+        let bytes = read.unwrap_or_else(|e| {
+            if file == "-" {
+                eprintln!("markterm: error reading stdin: {}", e);
+            } else {
+                eprintln!("markterm: {}: {}", file, e);
+            }
             process::exit(1);
-        })
+        });
+        encoding::decode(&bytes, cli.encoding)
     };
 
-    // Determine terminal dimensions
-    let (term_width, term_height) = terminal::size();
-    let width = cli.width.unwrap_or(term_width);
+    info!("read {} bytes from {}", content.len(), file);
+    let content = match cli.since_byte {
+        Some(offset) => markterm::tail_from(&content, offset).to_string(),
+        None => content,
+    };
+
+    if cli.outline {
+        for line in markterm::outline(&content) {
+            println!("{}", line);
+        }
+        return;
+    }
 
     // Determine if we should use color
+    // A legacy Windows console shows escape codes as garbage: no color, no pager
+    let plain_console = terminal::needs_plain_output(io::stdout().is_terminal(), terminal::enable_ansi);
+    if plain_console {
+        info!("terminal does not interpret escape codes, printing plain text");
+    }
     let use_color = match cli.theme.as_str() {
+        _ if cli.fold.is_some() || cli.teletype || plain_console => false,
         "none" => false,
         _ => {
             // Respect NO_COLOR env var
             std::env::var("NO_COLOR").is_err()
         }
     };
+    // The pager's scrollbar takes the last column
+    let pages = cli.fold.is_none() && cli.head.is_none() && !cli.no_pager && !cli.check;
+    let width = if cli.scrollbar && pages && io::stdout().is_terminal() {
+        width.saturating_sub(1).max(1)
+    } else {
+        width
+    };
+    info!("rendering at width {}, color {}", width, if use_color { "on" } else { "off" });
 
     // Parse and render
-    let events = parser::parse(&content);
-    let lines = renderer::render(events, width, use_color, cli.no_wrap);
+    let mut opts = RenderOptions {
+        use_color,
+        no_wrap: cli.no_wrap,
+        truncate: cli.truncate,
+        table_style: cli.table_style,
+        inline_code_style: cli.inline_code_style,
+        heading_style: cli.heading_style,
+        block_spacing: cli.block_spacing,
+        symbols: cli.symbols && !cli.teletype,
+        keep_markers: cli.keep_markers,
+        tab_width: cli.tab_width,
+        hanging_indent: cli.hanging_indent,
+        prose_width: cli.prose_width,
+        max_paragraph_lines: cli.max_paragraph_lines,
+        theme: Theme::named(&cli.theme).unwrap_or_default(),
+        link_refs: cli.link_refs,
+        ordered_style: cli.ordered_style,
+        table_captions: cli.table_captions,
+        show_comments: cli.show_comments,
+        table_header_repeat: cli.table_header_repeat,
+        ascii: cli.ascii || cli.teletype,
+        stats: cli.stats,
+        lead_style: cli.lead_style,
+        grep: cli.grep.clone(),
+        no_tables: cli.no_tables,
+        glossary: cli.glossary,
+        no_code: cli.no_code,
+        no_images: cli.no_images,
+        word_diff: cli.word_diff,
+        task_summary: cli.task_summary,
+        image_protocol: match cli.image_protocol.as_str() {
+            "auto" => terminal::detect_image_protocol(|name| std::env::var(name).ok()),
+            name => name.parse().unwrap_or_default(),
+        },
+        ..RenderOptions::default()
+    };
+    opts.raw_fences.extend(cli.raw_fence.iter().cloned());
+    if !cli.bullets.is_empty() {
+        opts.bullets = cli.bullets.clone();
+    }
+    info!("image protocol {:?}", opts.image_protocol);
+    let filename = if cli.eval.is_some() {
+        "(eval)".to_string()
+    } else if file == "-" {
+        "(stdin)".to_string()
+    } else {
+        file.to_string()
+    };
+
+    // Each column is rendered as a narrower document, then laid out side by side
+    let columns = cli.columns.filter(|&n| n > 1);
+    let render_width = columns.map_or(width, |n| renderer::column_width(width, n));
+    let glyphs = renderer::Glyphs::for_ascii(opts.ascii);
+    let doc = if cli.pager_only {
+        markterm::render_passthrough(&content)
+    } else if cli.raw {
+        markterm::render_source(&content, use_color)
+    } else {
+        match render_content(&cli, file, &content, render_width, &opts) {
+            Ok(doc) => doc,
+            Err(e) if cli.strict => {
+                eprintln!("markterm: {}: render error: {}", filename, e);
+                process::exit(1);
+            }
+            Err(_) => markterm::render_raw(&content),
+        }
+    };
+    let doc = match columns {
+        Some(n) => renderer::columns(doc, n, render_width as usize, &glyphs),
+        None => doc,
+    };
+    let lines = &doc.lines;
+
+    if lines.iter().all(|l| l.trim().is_empty()) {
+        if !cli.quiet {
+            eprintln!("markterm: {}: no renderable content", filename);
+        }
+        process::exit(EXIT_EMPTY);
+    }
+
+    if cli.code_plain_copy {
+        for (i, block) in doc.code_blocks.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            print!("{}", block.source);
+        }
+        return;
+    }
+
+    if cli.check {
+        for table in &doc.squeezed_tables {
+            eprintln!(
+                "markterm: {}: table at rendered line {} {}",
+                filename,
+                table.line + 1,
+                table.describe()
+            );
+        }
+        let over = renderer::overflowing_lines(lines, width as usize);
+        for (i, len) in &over {
+            eprintln!(
+                "markterm: {}: rendered line {} is {} columns wide (limit {})",
+                filename,
+                i + 1,
+                len,
+                width
+            );
+        }
+        process::exit(if over.is_empty() { 0 } else { EXIT_CHECK });
+    }
 
     // Output
     let is_tty = io::stdout().is_terminal();
-    if cli.no_pager || !is_tty {
-        // Dump to stdout
-        for line in &lines {
-            println!("{}", line);
-        }
+    if let Some(cols) = cli.fold {
+        let lines = renderer::fold(doc.lines, cols as usize);
+        let lines = match cli.head {
+            Some(max) => renderer::head(lines, max, &glyphs, false),
+            None => lines,
+        };
+        dump(&lines, &cli);
+    } else if let Some(max) = cli.head {
+        dump(&renderer::head(doc.lines, max, &glyphs, use_color), &cli);
+    } else if cli.no_pager || !is_tty || plain_console {
+        dump(lines, &cli);
     } else {
         // Interactive pager
-        let filename = if cli.file == "-" {
-            "(stdin)".to_string()
+        if cli.watch && file == "-" {
+            eprintln!("markterm: --watch needs a file, ignoring it for {}", filename);
+        }
+        let mut watcher = watch::FileWatcher::new(file);
+        let mut reload = || {
+            if !watcher.poll() {
+                return None;
+            }
+            // Keep showing the old version if the file is briefly unreadable mid-save
+            let content = encoding::decode(&fs::read(file).ok()?, cli.encoding);
+            let content = match cli.since_byte {
+                Some(offset) => markterm::tail_from(&content, offset).to_string(),
+                None => content,
+            };
+            let doc = match render_content(&cli, file, &content, render_width, &opts) {
+                Ok(doc) => doc,
+                Err(_) if cli.strict => return None,
+                Err(_) => markterm::render_raw(&content),
+            };
+            let doc = match columns {
+                Some(n) => renderer::columns(doc, n, render_width as usize, &glyphs),
+                None => doc,
+            };
+            Some((content, doc))
+        };
+        let reload: Option<pager::Reload> = if cli.watch && file != "-" {
+            Some(&mut reload)
         } else {
-            cli.file.clone()
+            None
         };
-        if let Err(e) = pager::run(&lines, term_height, &filename) {
+        let pager_opts = pager::PagerOptions {
+            open_links: cli.open_links,
+            empty_lines: cli.empty_lines,
+            filler_style: if use_color { opts.theme.filler.clone() } else { String::new() },
+            scrollbar: cli.scrollbar,
+            ascii: cli.ascii || cli.teletype,
+        };
+        if let Err(e) = pager::run(doc, &content, term_height, &filename, reload, pager_opts) {
             eprintln!("markterm: pager error: {}", e);
             process::exit(1);
         }
     }
 }
+
+/// Print rendered lines to stdout, trimming trailing padding unless asked not to.
+fn dump(lines: &[String], cli: &Cli) {
+    for line in lines {
+        let line = if cli.keep_trailing || cli.pager_only {
+            line.clone()
+        } else {
+            renderer::trim_trailing(line)
+        };
+        // Raw fallback output never went through the renderer, so convert it here
+        if cli.ascii || cli.teletype {
+            println!("{}", renderer::to_ascii(&line));
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Expand includes (if enabled), parse and render one version of the input.
+/// A panic while rendering is returned as an error message.
+fn render_content(
+    cli: &Cli,
+    file: &str,
+    content: &str,
+    width: u16,
+    opts: &RenderOptions,
+) -> Result<Rendered, String> {
+    let expanded;
+    let content = if cli.include {
+        let source = (file != "-").then(|| Path::new(file));
+        expanded = include::expand(content, source);
+        &expanded
+    } else {
+        content
+    };
+    let dated;
+    let content = if cli.dates || cli.date_format.is_some() {
+        dated = dates::substitute(content, dates::now(), cli.date_format.as_deref());
+        &dated
+    } else {
+        content
+    };
+    // Keep the default hook from printing a backtrace for a panic we recover from
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = markterm::try_render(content, width, opts);
+    panic::set_hook(hook);
+    result
+}
@@ -0,0 +1,340 @@
+//! Lay out display math (`$$ ... $$`) as multi-line text.
+//!
+//! Only a small subset of LaTeX is understood: fractions, square roots,
+//! sub- and superscripts, and common symbols. Anything else makes [`layout`]
+//! return `None`, and the caller shows the source instead.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A rectangle of text with one row marked as the baseline, which lines up
+/// with the baselines of its neighbors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Block {
+    rows: Vec<String>,
+    base: usize,
+}
+
+impl Block {
+    fn text(s: &str) -> Block {
+        Block {
+            rows: vec![s.to_string()],
+            base: 0,
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.rows.iter().map(|r| r.chars().count()).max().unwrap_or(0)
+    }
+
+    fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Rows below the baseline.
+    fn depth(&self) -> usize {
+        self.height() - self.base - 1
+    }
+}
+
+/// Pad `s` to `width` columns, centered.
+fn center(s: &str, width: usize) -> String {
+    let slack = width.saturating_sub(s.chars().count());
+    format!("{}{}{}", " ".repeat(slack / 2), s, " ".repeat(slack - slack / 2))
+}
+
+/// Join blocks left to right, aligned on their baselines.
+fn hcat(blocks: &[Block]) -> Block {
+    let base = blocks.iter().map(|b| b.base).max().unwrap_or(0);
+    let depth = blocks.iter().map(Block::depth).max().unwrap_or(0);
+    let mut rows = vec![String::new(); base + depth + 1];
+    for block in blocks {
+        let width = block.width();
+        let top = base - block.base;
+        for (i, row) in rows.iter_mut().enumerate() {
+            let cell = i.checked_sub(top).and_then(|r| block.rows.get(r));
+            row.push_str(&center(cell.map_or("", String::as_str), width));
+        }
+    }
+    Block { rows, base }
+}
+
+/// Numerator over a bar over denominator, the bar on the baseline.
+fn fraction(num: Block, den: Block, bar: &str) -> Block {
+    let width = num.width().max(den.width()) + 2;
+    let mut rows: Vec<String> = num.rows.iter().map(|r| center(r, width)).collect();
+    let base = rows.len();
+    rows.push(bar.repeat(width));
+    rows.extend(den.rows.iter().map(|r| center(r, width)));
+    Block { rows, base }
+}
+
+/// `√` in front of the radicand, with a bar over it.
+fn root(radicand: Block) -> Block {
+    let width = radicand.width();
+    let mut rows = vec![format!(" {}", "_".repeat(width))];
+    for (i, row) in radicand.rows.iter().enumerate() {
+        let sign = if i == radicand.base { "√" } else { " " };
+        rows.push(format!("{}{}", sign, center(row, width)));
+    }
+    Block {
+        rows,
+        base: radicand.base + 1,
+    }
+}
+
+/// A column holding a superscript above the baseline and a subscript below.
+fn scripts(sup: Option<Block>, sub: Option<Block>) -> Block {
+    let width = sup.iter().chain(&sub).map(Block::width).max().unwrap_or(0);
+    let left = |r: &String| format!("{:<width$}", r, width = width);
+    let mut rows: Vec<String> = sup.iter().flat_map(|b| b.rows.iter().map(left)).collect();
+    let base = rows.len();
+    rows.push(" ".repeat(width));
+    rows.extend(sub.iter().flat_map(|b| b.rows.iter().map(left)));
+    Block { rows, base }
+}
+
+/// The character for a symbol command like `\alpha`.
+fn symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" | "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "rho" => "ρ",
+        "sigma" => "σ",
+        "tau" => "τ",
+        "phi" | "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        "sum" => "Σ",
+        "prod" => "∏",
+        "int" => "∫",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "infty" => "∞",
+        "cdot" => "·",
+        "ldots" | "cdots" | "dots" => "…",
+        _ => return None,
+    })
+}
+
+/// Operators and relations, drawn with a space on each side.
+fn spaced_symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "times" => "×",
+        "div" => "÷",
+        "pm" => "±",
+        "mp" => "∓",
+        "le" | "leq" => "≤",
+        "ge" | "geq" => "≥",
+        "ne" | "neq" => "≠",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "to" | "rightarrow" => "→",
+        "leftarrow" => "←",
+        "in" => "∈",
+        _ => return None,
+    })
+}
+
+/// How deeply groups, commands and scripts may nest before [`layout`] gives
+/// up, so hostile input can't overflow the stack.
+const MAX_DEPTH: usize = 32;
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    bar: &'a str,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    /// Atoms up to the end of input or a closing brace, laid out in a row.
+    fn row(&mut self) -> Option<Block> {
+        let mut blocks: Vec<Block> = Vec::new();
+        loop {
+            self.skip_space();
+            match self.chars.peek() {
+                None | Some('}') => break,
+                Some('^' | '_') => {
+                    let base = blocks.pop().unwrap_or_else(|| Block::text(""));
+                    let (sup, sub) = self.scripts()?;
+                    blocks.push(hcat(&[base, scripts(sup, sub)]));
+                }
+                Some(_) => {
+                    let first = blocks.is_empty();
+                    blocks.push(self.atom(first)?);
+                }
+            }
+        }
+        Some(hcat(&blocks))
+    }
+
+    /// A run of `^x` and `_y`, at most one of each.
+    fn scripts(&mut self) -> Option<(Option<Block>, Option<Block>)> {
+        let (mut sup, mut sub) = (None, None);
+        loop {
+            self.skip_space();
+            let slot = match self.chars.peek() {
+                Some('^') => &mut sup,
+                Some('_') => &mut sub,
+                _ => return Some((sup, sub)),
+            };
+            if slot.is_some() {
+                return None;
+            }
+            self.chars.next();
+            *slot = Some(self.argument()?);
+        }
+    }
+
+    /// A braced group or a single atom, as taken by `\frac` and scripts.
+    fn argument(&mut self) -> Option<Block> {
+        self.skip_space();
+        self.atom(true)
+    }
+
+    /// One symbol, command or group. `first` is set at the start of a row,
+    /// where `+` and `-` are signs rather than operators.
+    fn atom(&mut self, first: bool) -> Option<Block> {
+        if self.depth == MAX_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        let block = self.nested_atom(first);
+        self.depth -= 1;
+        block
+    }
+
+    fn nested_atom(&mut self, first: bool) -> Option<Block> {
+        match self.chars.next()? {
+            '{' => {
+                let block = self.row()?;
+                self.chars.next_if_eq(&'}')?;
+                Some(block)
+            }
+            '\\' => self.command(),
+            c @ ('=' | '<' | '>') => Some(Block::text(&format!(" {} ", c))),
+            c @ ('+' | '-') if !first => Some(Block::text(&format!(" {} ", c))),
+            '&' | '#' | '%' | '~' | '$' | '}' => None,
+            c => Some(Block::text(&c.to_string())),
+        }
+    }
+
+    fn command(&mut self) -> Option<Block> {
+        let mut name = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphabetic()) {
+            name.push(c);
+        }
+        if name.is_empty() {
+            return match self.chars.next()? {
+                ',' | ';' | ' ' => Some(Block::text(" ")),
+                c @ ('{' | '}') => Some(Block::text(&c.to_string())),
+                _ => None,
+            };
+        }
+        match name.as_str() {
+            "frac" | "dfrac" | "tfrac" => {
+                let num = self.argument()?;
+                let den = self.argument()?;
+                Some(fraction(num, den, self.bar))
+            }
+            "sqrt" => {
+                self.skip_space();
+                // Roots other than square (`\sqrt[3]{x}`) aren't drawn
+                if self.chars.peek() == Some(&'[') {
+                    return None;
+                }
+                Some(root(self.argument()?))
+            }
+            _ => match (symbol(&name), spaced_symbol(&name)) {
+                (Some(s), _) => Some(Block::text(s)),
+                (_, Some(s)) => Some(Block::text(&format!(" {} ", s))),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Lay out `source` (the LaTeX between `$$` delimiters) as rows of text of
+/// equal width, using `bar` for fraction bars. `None` if it uses anything
+/// beyond the supported subset.
+pub fn layout(source: &str, bar: &str) -> Option<Vec<String>> {
+    let mut parser = Parser {
+        chars: source.chars().peekable(),
+        bar,
+        depth: 0,
+    };
+    let block = parser.row()?;
+    // A stray closing brace ends the row early
+    if parser.chars.next().is_some() || block.width() == 0 {
+        return None;
+    }
+    let rows: Vec<String> = block.rows.iter().map(|r| r.trim_end().to_string()).collect();
+    Some(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fraction() {
+        assert_eq!(layout(r"\frac{a}{b}", "─").unwrap(), [" a", "───", " b"]);
+    }
+
+    #[test]
+    fn test_scripts_and_symbols() {
+        assert_eq!(layout(r"x_i^2 = \alpha", "─").unwrap(), [" 2", "x  = α", " i"]);
+        assert_eq!(layout("e^{-x}", "-").unwrap(), [" -x", "e"]);
+    }
+
+    #[test]
+    fn test_fraction_aligns_with_neighbors() {
+        let rows = layout(r"y = \frac{1}{\sqrt{x}} + 1", "─").unwrap();
+        assert_eq!(rows, ["     1", "y = ──── + 1", "      _", "     √x"]);
+    }
+
+    #[test]
+    fn test_unsupported_falls_back() {
+        assert_eq!(layout(r"\begin{matrix} a & b \end{matrix}", "─"), None);
+        assert_eq!(layout(r"\sqrt[3]{x}", "─"), None);
+        assert_eq!(layout(r"\frac{a}{b", "─"), None);
+        assert_eq!(layout("a}", "─"), None);
+    }
+
+    #[test]
+    fn test_deep_nesting_falls_back() {
+        let nested = format!("{}x{}", "{".repeat(200_000), "}".repeat(200_000));
+        assert_eq!(layout(&nested, "─"), None);
+        assert_eq!(layout(&format!("{}x", r"\sqrt ".repeat(200_000)), "─"), None);
+        assert_eq!(layout(&"x^".repeat(200_000), "─"), None);
+        // Modest nesting still lays out
+        assert_eq!(layout(&format!("{}x{}", "{".repeat(10), "}".repeat(10)), "─").unwrap(), ["x"]);
+    }
+}
@@ -1,4 +1,4 @@
-/// A `more`-style pager: displays lines one page at a time with keyboard navigation.
+//! A `more`-style pager: displays lines one page at a time with keyboard navigation.
 
 use crossterm::{
     cursor,
@@ -6,12 +6,333 @@ use crossterm::{
     execute,
     terminal::{self, ClearType},
 };
+use std::fs;
 use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
+use crate::renderer::{self, FootnoteSpan, Glyphs, LinkSpan, Rendered, TruncatePosition};
 use crate::style;
 
-pub fn run(lines: &[String], term_height: u16, filename: &str) -> io::Result<()> {
+/// Range of line indices visible on a page starting at `offset`.
+pub fn visible_range(total_lines: usize, offset: usize, page_height: usize) -> Range<usize> {
+    let start = offset.min(total_lines);
+    start..(offset + page_height).min(total_lines)
+}
+
+/// What fills the rows below the end of a short page.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyLines {
+    /// `~`, as in vi
+    #[default]
+    Tilde,
+    Blank,
+}
+
+impl FromStr for EmptyLines {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tilde" => Ok(EmptyLines::Tilde),
+            "blank" => Ok(EmptyLines::Blank),
+            _ => Err(format!("unknown empty line style '{}' (expected tilde, blank)", s)),
+        }
+    }
+}
+
+/// Interactive behavior and look of the pager.
+#[derive(Clone, Debug, Default)]
+pub struct PagerOptions {
+    /// Enter opens the selected link instead of showing its URL
+    pub open_links: bool,
+    pub empty_lines: EmptyLines,
+    /// Style for the `~` filler lines; empty for no color
+    pub filler_style: String,
+    /// Draw a scrollbar in the rightmost column
+    pub scrollbar: bool,
+    /// Draw the scrollbar with `#` and `|` instead of block characters
+    pub ascii: bool,
+}
+
+/// The text drawn on a row past the end of the document.
+fn filler_line(opts: &PagerOptions) -> String {
+    match opts.empty_lines {
+        EmptyLines::Blank => String::new(),
+        EmptyLines::Tilde if opts.filler_style.is_empty() => "~".to_string(),
+        EmptyLines::Tilde => format!("{}~{}", opts.filler_style, style::RESET),
+    }
+}
+
+/// Columns taken by the line number gutter for a document of `total_lines`:
+/// the widest number plus a separating space.
+pub fn gutter_width(total_lines: usize) -> usize {
+    total_lines.max(1).to_string().len() + 1
+}
+
+/// Rows of the scrollbar covered by the thumb, for a page of `page_height`
+/// rows at `offset` into `total` lines. The thumb is at least one row, and
+/// reaches the bottom exactly when the page does.
+pub fn scrollbar_thumb(offset: usize, page_height: usize, total: usize) -> Range<usize> {
+    if total <= page_height || page_height == 0 {
+        return 0..page_height;
+    }
+    let size = (page_height * page_height).div_ceil(total).clamp(1, page_height);
+    let max_offset = total - page_height;
+    let travel = page_height - size;
+    let start = (offset.min(max_offset) * travel + max_offset / 2) / max_offset;
+    start..start + size
+}
+
+/// URL schemes `--open-links` hands to the OS handler; anything else, like
+/// `javascript:` or a custom scheme, could run a command. `file:` is left out
+/// too, since the handler may run a local executable or `.desktop` file.
+const OPENABLE_SCHEMES: &[&str] = &["http", "https"];
+
+/// Whether `url` has one of `schemes`, compared case-insensitively.
+/// Relative links have no scheme and are never allowed.
+fn scheme_allowed(url: &str, schemes: &[&str]) -> bool {
+    match url.trim().split_once(':') {
+        Some((scheme, _)) => schemes.iter().any(|s| s.eq_ignore_ascii_case(scheme)),
+        None => false,
+    }
+}
+
+/// Open `url` with the OS default handler, without going through a shell.
+fn open_url(url: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(drop)
+}
+
+/// Definition line of the first footnote referenced on `page`.
+fn footnote_target(footnotes: &[FootnoteSpan], page: Range<usize>) -> Option<usize> {
+    footnotes
+        .iter()
+        .filter_map(|note| Some((*note.refs.iter().find(|line| page.contains(line))?, note.definition?)))
+        .min()
+        .map(|(_, definition)| definition)
+}
+
+/// Index of the next link on the page after `current`, wrapping to the first
+/// link on the page. Returns `None` if the page has no links.
+fn next_link(links: &[LinkSpan], page: Range<usize>, current: Option<usize>) -> Option<usize> {
+    let on_page: Vec<usize> = (0..links.len())
+        .filter(|&i| page.contains(&links[i].line))
+        .collect();
+    match current {
+        Some(cur) => on_page
+            .iter()
+            .copied()
+            .find(|&i| i > cur)
+            .or_else(|| on_page.first().copied()),
+        None => on_page.first().copied(),
+    }
+}
+
+/// Index of the previous link on the page before `current`, wrapping to the last.
+fn prev_link(links: &[LinkSpan], page: Range<usize>, current: Option<usize>) -> Option<usize> {
+    let on_page: Vec<usize> = (0..links.len())
+        .filter(|&i| page.contains(&links[i].line))
+        .collect();
+    match current {
+        Some(cur) => on_page
+            .iter()
+            .rev()
+            .copied()
+            .find(|&i| i < cur)
+            .or_else(|| on_page.last().copied()),
+        None => on_page.last().copied(),
+    }
+}
+
+/// For a scroll from `old` to `new` offset, the signed line delta and the
+/// screen rows newly exposed, or `None` when a full redraw is needed.
+fn scroll_rows(old: usize, new: usize, page_height: usize) -> Option<(isize, Range<usize>)> {
+    let delta = new as isize - old as isize;
+    if delta == 0 || delta.unsigned_abs() >= page_height {
+        return None;
+    }
+    let rows = if delta > 0 {
+        page_height - delta as usize..page_height
+    } else {
+        0..delta.unsigned_abs()
+    };
+    Some((delta, rows))
+}
+
+/// How often the pager checks for a reload or a termination signal while
+/// waiting for keys.
+const RELOAD_POLL: Duration = Duration::from_millis(100);
+
+/// Runs a teardown closure when dropped, so the terminal is restored however
+/// the pager exits: normally, through `?`, or by panicking.
+pub struct RestoreGuard<F: FnOnce()> {
+    restore: Option<F>,
+}
+
+impl<F: FnOnce()> RestoreGuard<F> {
+    pub fn new(restore: F) -> Self {
+        Self {
+            restore: Some(restore),
+        }
+    }
+}
+
+impl<F: FnOnce()> Drop for RestoreGuard<F> {
+    fn drop(&mut self) {
+        if let Some(restore) = self.restore.take() {
+            restore();
+        }
+    }
+}
+
+/// Set by the signal handler; the pager loop quits when it sees it.
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+mod signals {
+    use std::os::raw::c_int;
+    use std::sync::atomic::Ordering;
+
+    const SIGHUP: c_int = 1;
+    const SIGINT: c_int = 2;
+    const SIGTERM: c_int = 15;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    }
+
+    extern "C" fn handle(_: c_int) {
+        // Only an atomic store: anything more isn't async-signal-safe
+        super::TERMINATED.store(true, Ordering::SeqCst);
+    }
+
+    /// Turn hangup, interrupt and terminate signals into a clean quit.
+    pub fn install() {
+        for signum in [SIGHUP, SIGINT, SIGTERM] {
+            // SAFETY: `handle` only performs an atomic store, which is
+            // async-signal-safe, and these signal numbers are the same on all
+            // Unix platforms we build for.
+            unsafe {
+                signal(signum, handle);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod signals {
+    pub fn install() {}
+}
+
+/// Re-reads and re-renders the document, returning the new source and its
+/// rendering; returns `None` when nothing changed.
+pub type Reload<'a> = &'a mut dyn FnMut() -> Option<(String, Rendered)>;
+
+/// Scroll offset to keep after reloading a document of `total_lines` lines:
+/// the same position where possible, clamped so the last page stays full.
+fn reload_offset(offset: usize, total_lines: usize, page_height: usize) -> usize {
+    offset.min(total_lines.saturating_sub(page_height))
+}
+
+/// Line of text typed at the status line, e.g. a filename to save to.
+struct Prompt {
+    label: String,
+    input: String,
+    action: PromptAction,
+    /// Set when asking whether to replace this existing file
+    overwrite: Option<String>,
+}
+
+/// What to do with a prompt's input once it is submitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PromptAction {
+    SaveSource,
+    SaveRendered,
+}
+
+/// Result of feeding one key to a [`Prompt`].
+#[derive(Debug, PartialEq, Eq)]
+enum PromptEvent {
+    Editing,
+    Submit(String),
+    Cancel,
+}
+
+impl Prompt {
+    fn new(label: &str, action: PromptAction) -> Self {
+        Prompt {
+            label: label.to_string(),
+            input: String::new(),
+            action,
+            overwrite: None,
+        }
+    }
+
+    /// Ask before `action` replaces the existing file at `path`.
+    fn confirm_overwrite(action: PromptAction, path: String) -> Self {
+        Prompt {
+            overwrite: Some(path.clone()),
+            ..Prompt::new(&format!("{} exists, overwrite? [y/N] ", path), action)
+        }
+    }
+
+    /// Update the input for `key`. Enter submits; Esc, Ctrl-C or Enter on
+    /// empty input cancel.
+    fn handle_key(&mut self, key: KeyEvent) -> PromptEvent {
+        match key.code {
+            KeyCode::Esc => PromptEvent::Cancel,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => PromptEvent::Cancel,
+            KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.input.clear();
+                PromptEvent::Editing
+            }
+            KeyCode::Enter if self.input.is_empty() => PromptEvent::Cancel,
+            KeyCode::Enter => PromptEvent::Submit(std::mem::take(&mut self.input)),
+            KeyCode::Backspace => {
+                self.input.pop();
+                PromptEvent::Editing
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.input.push(c);
+                PromptEvent::Editing
+            }
+            _ => PromptEvent::Editing,
+        }
+    }
+}
+
+/// Page through `doc`, rendered from `source`. With `reload`, the pager stays
+/// interactive even for short documents and redraws whenever `reload`
+/// produces a new rendering.
+pub fn run(
+    doc: Rendered,
+    source: &str,
+    term_height: u16,
+    filename: &str,
+    reload: Option<Reload>,
+    opts: PagerOptions,
+) -> io::Result<()> {
     let mut stdout = io::stdout();
+    let lines = &doc.lines;
 
     // Page height: terminal height minus 1 for the status line
     let page_height = (term_height.saturating_sub(1)) as usize;
@@ -23,184 +344,385 @@ pub fn run(lines: &[String], term_height: u16, filename: &str) -> io::Result<()>
         return Ok(());
     }
 
-    let total_lines = lines.len();
-
-    // If content fits on one screen, just print it
-    if total_lines <= page_height {
+    if prints_directly(lines.len(), page_height, reload.is_some()) {
         for line in lines {
             writeln!(stdout, "{}", line)?;
         }
         return Ok(());
     }
 
-    // Enter raw mode for interactive paging
+    let (term_width, _) = terminal::size().unwrap_or((80, 24));
+    let mut view = View {
+        doc,
+        source: source.to_string(),
+        filename,
+        term_width: term_width as usize,
+        page_height,
+        offset: 0,
+        selected_link: None,
+        message: None,
+        prompt: None,
+        opts,
+        footnote_return: None,
+        line_numbers: false,
+    };
+
+    // Enter raw mode for interactive paging, leaving it however the loop ends
+    signals::install();
     terminal::enable_raw_mode()?;
-    // Ensure we restore terminal on panic
-    let result = run_pager_loop(&mut stdout, lines, page_height, total_lines, filename);
-    terminal::disable_raw_mode()?;
-    // Move to a new line after the status bar
-    execute!(stdout, cursor::MoveToColumn(0))?;
-    writeln!(stdout)?;
+    let _guard = RestoreGuard::new(|| {
+        let mut stdout = io::stdout();
+        let _ = terminal::disable_raw_mode();
+        // Move to a new line after the status bar
+        let _ = execute!(stdout, cursor::MoveToColumn(0));
+        let _ = writeln!(stdout);
+    });
+    run_pager_loop(&mut stdout, &mut view, reload)
+}
 
-    result
+/// Whether a document of `total_lines` is printed as is rather than paged:
+/// only when it fits on one screen and nothing keeps the session alive. A
+/// watched document is always paged, so if the terminal later shrinks below
+/// it, it can be scrolled like any long document.
+fn prints_directly(total_lines: usize, page_height: usize, watching: bool) -> bool {
+    total_lines <= page_height && !watching
 }
 
-fn run_pager_loop(
-    stdout: &mut io::Stdout,
-    lines: &[String],
+/// Scroll position and transient UI state for one pager session.
+struct View<'a> {
+    doc: Rendered,
+    source: String,
+    filename: &'a str,
+    /// Terminal columns, kept current by `resize`
+    term_width: usize,
     page_height: usize,
-    total_lines: usize,
-    filename: &str,
-) -> io::Result<()> {
-    let mut offset: usize = 0;
+    offset: usize,
+    selected_link: Option<usize>,
+    message: Option<String>,
+    prompt: Option<Prompt>,
+    opts: PagerOptions,
+    /// Offset to go back to after jumping to a footnote
+    footnote_return: Option<usize>,
+    /// Show the rendered line number in a gutter on the left
+    line_numbers: bool,
+}
+
+impl View<'_> {
+    fn lines(&self) -> &[String] {
+        &self.doc.lines
+    }
+
+    fn links(&self) -> &[LinkSpan] {
+        &self.doc.links
+    }
+
+    fn total_lines(&self) -> usize {
+        self.doc.lines.len()
+    }
 
+    /// Swap in a re-rendered document, keeping the scroll position. The link
+    /// selection and any message about it belonged to the old document.
+    fn replace(&mut self, source: String, doc: Rendered) {
+        self.offset = reload_offset(self.offset, doc.lines.len(), self.page_height);
+        self.selected_link = None;
+        self.message = None;
+        self.footnote_return = None;
+        self.source = source;
+        self.doc = doc;
+    }
+
+    /// Act on `input` typed at `prompt`: save to the named file, first asking
+    /// before replacing one that exists.
+    fn submit(&mut self, prompt: Prompt, input: String) {
+        match prompt.overwrite {
+            Some(path) if input.eq_ignore_ascii_case("y") => {
+                self.message = Some(self.save(prompt.action, &path));
+            }
+            Some(path) => self.message = Some(format!(" {} not saved ", path)),
+            None if Path::new(&input).exists() => {
+                self.prompt = Some(Prompt::confirm_overwrite(prompt.action, input));
+            }
+            None => self.message = Some(self.save(prompt.action, &input)),
+        }
+    }
+
+    /// Write the source or the rendered lines to `path`, returning a status message.
+    fn save(&self, action: PromptAction, path: &str) -> String {
+        let result = match action {
+            PromptAction::SaveSource => fs::write(path, &self.source),
+            PromptAction::SaveRendered => fs::write(path, self.doc.lines.join("\n") + "\n"),
+        };
+        match result {
+            Ok(()) => format!(" saved to {} ", path),
+            Err(e) => format!(" {}: {} ", path, e),
+        }
+    }
+
+    /// Adapt to a terminal now `term_width` by `term_height`: one row stays
+    /// reserved for the status line, and the offset is clamped so the last
+    /// page is full.
+    fn resize(&mut self, term_width: u16, term_height: u16) {
+        self.term_width = term_width as usize;
+        self.page_height = (term_height.saturating_sub(1) as usize).max(1);
+        self.offset = reload_offset(self.offset, self.total_lines(), self.page_height);
+    }
+
+    fn max_offset(&self) -> usize {
+        self.total_lines().saturating_sub(self.page_height)
+    }
+
+    fn page(&self) -> Range<usize> {
+        visible_range(self.total_lines(), self.offset, self.page_height)
+    }
+}
+
+fn run_pager_loop(stdout: &mut io::Stdout, view: &mut View, mut reload: Option<Reload>) -> io::Result<()> {
     // Initial draw
-    draw_page(stdout, lines, offset, page_height, total_lines, filename)?;
+    draw_page(stdout, view)?;
 
     loop {
-        if let Event::Key(key) = event::read()? {
-            match key {
-                // Quit
-                KeyEvent {
-                    code: KeyCode::Char('q'),
-                    ..
-                }
-                | KeyEvent {
-                    code: KeyCode::Esc, ..
-                } => break,
-
-                // Ctrl-C
-                KeyEvent {
-                    code: KeyCode::Char('c'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                } => break,
-
-                // Next page (space, Page Down)
-                KeyEvent {
-                    code: KeyCode::Char(' '),
-                    ..
-                }
-                | KeyEvent {
-                    code: KeyCode::PageDown,
-                    ..
-                }
-                | KeyEvent {
-                    code: KeyCode::Char('f'),
-                    modifiers: KeyModifiers::CONTROL,
-                    ..
-                } => {
-                    let max_offset = total_lines.saturating_sub(page_height);
-                    offset = (offset + page_height).min(max_offset);
-                    draw_page(stdout, lines, offset, page_height, total_lines, filename)?;
+        if TERMINATED.load(Ordering::SeqCst) {
+            break;
+        }
+        if !event::poll(RELOAD_POLL)? {
+            if let Some(reload) = reload.as_mut() {
+                if let Some((source, doc)) = reload() {
+                    view.replace(source, doc);
+                    draw_page(stdout, view)?;
                 }
+            }
+            continue;
+        }
 
-                // Previous page (b, Page Up)
-                KeyEvent {
-                    code: KeyCode::Char('b'),
-                    ..
-                }
-                | KeyEvent {
-                    code: KeyCode::PageUp,
-                    ..
-                }
-                => {
-                    offset = offset.saturating_sub(page_height);
-                    draw_page(stdout, lines, offset, page_height, total_lines, filename)?;
-                }
+        let key = match event::read()? {
+            Event::Key(key) => key,
+            Event::Resize(cols, rows) => {
+                view.resize(cols, rows);
+                draw_page(stdout, view)?;
+                continue;
+            }
+            _ => continue,
+        };
+        crate::debug!("key {:?} {:?} at offset {}", key.code, key.modifiers, view.offset);
+        view.message = None;
 
-                // Next line (Enter, Down, j)
-                KeyEvent {
-                    code: KeyCode::Enter,
-                    ..
-                }
-                | KeyEvent {
-                    code: KeyCode::Down, ..
-                }
-                | KeyEvent {
-                    code: KeyCode::Char('j'),
-                    ..
-                } => {
-                    let max_offset = total_lines.saturating_sub(page_height);
-                    if offset < max_offset {
-                        offset += 1;
-                        draw_page(stdout, lines, offset, page_height, total_lines, filename)?;
-                    }
+        // A status-line prompt takes all keys until it is submitted or cancelled
+        if let Some(prompt) = view.prompt.as_mut() {
+            match prompt.handle_key(key) {
+                PromptEvent::Editing => {}
+                PromptEvent::Submit(input) => {
+                    let prompt = view.prompt.take().expect("prompt being typed");
+                    view.submit(prompt, input);
                 }
+                PromptEvent::Cancel => view.prompt = None,
+            }
+            draw_page(stdout, view)?;
+            continue;
+        }
 
-                // Previous line (Up, k)
-                KeyEvent {
-                    code: KeyCode::Up, ..
-                }
-                | KeyEvent {
-                    code: KeyCode::Char('k'),
-                    ..
-                } => {
-                    if offset > 0 {
-                        offset -= 1;
-                        draw_page(stdout, lines, offset, page_height, total_lines, filename)?;
-                    }
-                }
+        match key {
+            // Quit
+            KeyEvent {
+                code: KeyCode::Char('q'),
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Esc, ..
+            } => break,
 
-                // Go to top (g, Home)
-                KeyEvent {
-                    code: KeyCode::Char('g'),
-                    ..
-                }
-                | KeyEvent {
-                    code: KeyCode::Home, ..
-                } => {
-                    offset = 0;
-                    draw_page(stdout, lines, offset, page_height, total_lines, filename)?;
-                }
+            // Ctrl-C
+            KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => break,
 
-                // Go to bottom (G, End)
-                KeyEvent {
-                    code: KeyCode::Char('G'),
-                    ..
-                }
-                | KeyEvent {
-                    code: KeyCode::End, ..
-                } => {
-                    offset = total_lines.saturating_sub(page_height);
-                    draw_page(stdout, lines, offset, page_height, total_lines, filename)?;
-                }
+            // Next page (space, Page Down)
+            KeyEvent {
+                code: KeyCode::Char(' '),
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::PageDown,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            } => {
+                view.offset = (view.offset + view.page_height).min(view.max_offset());
+                draw_page(stdout, view)?;
+            }
+
+            // Toggle the line number gutter (#); lines too wide for what is left get cut
+            KeyEvent {
+                code: KeyCode::Char('#'),
+                ..
+            } => {
+                view.line_numbers = !view.line_numbers;
+                draw_page(stdout, view)?;
+            }
 
-                // Half page down (d, Ctrl-d)
-                KeyEvent {
-                    code: KeyCode::Char('d'),
-                    ..
-                } => {
-                    let max_offset = total_lines.saturating_sub(page_height);
-                    offset = (offset + page_height / 2).min(max_offset);
-                    draw_page(stdout, lines, offset, page_height, total_lines, filename)?;
+            // Jump to the first footnote referenced on the page, or back (f)
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                ..
+            } => {
+                if let Some(offset) = view.footnote_return.take() {
+                    view.offset = offset;
+                } else if let Some(line) = footnote_target(&view.doc.footnotes, view.page()) {
+                    view.footnote_return = Some(view.offset);
+                    view.offset = line.min(view.max_offset());
                 }
+                draw_page(stdout, view)?;
+            }
 
-                // Half page up (u, Ctrl-u)
-                KeyEvent {
-                    code: KeyCode::Char('u'),
-                    ..
-                } => {
-                    offset = offset.saturating_sub(page_height / 2);
-                    draw_page(stdout, lines, offset, page_height, total_lines, filename)?;
+            // Previous page (b, Page Up)
+            KeyEvent {
+                code: KeyCode::Char('b'),
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::PageUp,
+                ..
+            } => {
+                view.offset = view.offset.saturating_sub(view.page_height);
+                draw_page(stdout, view)?;
+            }
+
+            // Next link on the page (Tab)
+            KeyEvent {
+                code: KeyCode::Tab, ..
+            } => {
+                view.selected_link = next_link(view.links(), view.page(), view.selected_link);
+                draw_page(stdout, view)?;
+            }
+
+            // Previous link on the page (Shift-Tab)
+            KeyEvent {
+                code: KeyCode::BackTab,
+                ..
+            } => {
+                view.selected_link = prev_link(view.links(), view.page(), view.selected_link);
+                draw_page(stdout, view)?;
+            }
+
+            // Open or show the selected link (Enter with a link selected)
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            } if view.selected_link.is_some() => {
+                if let Some(link) = view.selected_link.and_then(|i| view.links().get(i)) {
+                    let url = &link.url;
+                    view.message = Some(if !view.opts.open_links {
+                        format!(" {} ", url)
+                    } else if !scheme_allowed(url, OPENABLE_SCHEMES) {
+                        format!(" can't open {} ", url)
+                    } else {
+                        match open_url(url) {
+                            Ok(()) => format!(" opened {} ", url),
+                            Err(e) => format!(" {}: {} ", url, e),
+                        }
+                    });
                 }
+                draw_page(stdout, view)?;
+            }
+
+            // Next line (Enter, Down, j)
+            KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Down, ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('j'),
+                ..
+            } if view.offset < view.max_offset() => {
+                view.offset += 1;
+                draw_scrolled(stdout, view, view.offset - 1)?;
+            }
+
+            // Previous line (Up, k)
+            KeyEvent {
+                code: KeyCode::Up, ..
+            }
+            | KeyEvent {
+                code: KeyCode::Char('k'),
+                ..
+            } if view.offset > 0 => {
+                view.offset -= 1;
+                draw_scrolled(stdout, view, view.offset + 1)?;
+            }
+
+            // Go to top (g, Home)
+            KeyEvent {
+                code: KeyCode::Char('g'),
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::Home, ..
+            } => {
+                view.offset = 0;
+                draw_page(stdout, view)?;
+            }
+
+            // Go to bottom (G, End)
+            KeyEvent {
+                code: KeyCode::Char('G'),
+                ..
+            }
+            | KeyEvent {
+                code: KeyCode::End, ..
+            } => {
+                view.offset = view.max_offset();
+                draw_page(stdout, view)?;
+            }
+
+            // Half page down (d, Ctrl-d)
+            KeyEvent {
+                code: KeyCode::Char('d'),
+                ..
+            } => {
+                view.offset = (view.offset + view.page_height / 2).min(view.max_offset());
+                draw_page(stdout, view)?;
+            }
+
+            // Save the source (s) or the rendered output (S) to a file
+            KeyEvent {
+                code: KeyCode::Char('s'),
+                ..
+            } => {
+                view.prompt = Some(Prompt::new("Save source to: ", PromptAction::SaveSource));
+                draw_page(stdout, view)?;
+            }
+            KeyEvent {
+                code: KeyCode::Char('S'),
+                ..
+            } => {
+                view.prompt = Some(Prompt::new("Save rendered to: ", PromptAction::SaveRendered));
+                draw_page(stdout, view)?;
+            }
 
-                _ => {}
+            // Half page up (u, Ctrl-u)
+            KeyEvent {
+                code: KeyCode::Char('u'),
+                ..
+            } => {
+                view.offset = view.offset.saturating_sub(view.page_height / 2);
+                draw_page(stdout, view)?;
             }
+
+            _ => {}
         }
     }
 
     Ok(())
 }
 
-fn draw_page(
-    stdout: &mut io::Stdout,
-    lines: &[String],
-    offset: usize,
-    page_height: usize,
-    total_lines: usize,
-    filename: &str,
-) -> io::Result<()> {
+fn draw_page(stdout: &mut io::Stdout, view: &View) -> io::Result<()> {
     // Move cursor to top-left and clear screen
     execute!(
         stdout,
@@ -208,54 +730,517 @@ fn draw_page(
         terminal::Clear(ClearType::All)
     )?;
 
-    // Display lines for this page
-    let end = (offset + page_height).min(total_lines);
-    for i in offset..end {
-        writeln!(stdout, "{}\r", &lines[i])?;
+    // Display lines for this page, padding with filler lines if the page is not full
+    for row in 0..view.page_height {
+        draw_row(stdout, view, row)?;
+        writeln!(stdout, "\r")?;
     }
 
-    // Pad remaining lines if page is not full
-    for _ in (end - offset)..page_height {
-        writeln!(stdout, "~\r")?;
-    }
+    draw_status(stdout, view)
+}
 
-    // Status line
-    let percentage = if total_lines == 0 {
-        100
-    } else {
-        ((end as f64 / total_lines as f64) * 100.0) as usize
+/// Redraw after scrolling from `old_offset`: small scrolls shift the page
+/// region and draw only the exposed lines, avoiding a full-screen flicker.
+fn draw_scrolled(stdout: &mut io::Stdout, view: &View, old_offset: usize) -> io::Result<()> {
+    let Some((delta, rows)) = scroll_rows(old_offset, view.offset, view.page_height) else {
+        return draw_page(stdout, view);
     };
 
-    let status = format!(
-        " {} | lines {}-{} of {} ({}%) ",
-        filename,
-        offset + 1,
-        end,
-        total_lines,
-        percentage
-    );
+    // Limit scrolling to the page rows so the status line stays put
+    write!(stdout, "\x1b[1;{}r", view.page_height)?;
+    if delta > 0 {
+        execute!(stdout, terminal::ScrollUp(delta as u16))?;
+    } else {
+        execute!(stdout, terminal::ScrollDown(delta.unsigned_abs() as u16))?;
+    }
+    write!(stdout, "\x1b[r")?;
 
-    let help = " [Space] next  [b] back  [q] quit ";
+    for row in rows.clone() {
+        execute!(
+            stdout,
+            cursor::MoveTo(0, row as u16),
+            terminal::Clear(ClearType::CurrentLine)
+        )?;
+        draw_row(stdout, view, row)?;
+    }
+    // The thumb moved, so the shifted rows need their scrollbar cell redrawn
+    if view.opts.scrollbar {
+        for row in (0..view.page_height).filter(|row| !rows.contains(row)) {
+            execute!(stdout, cursor::MoveTo(0, row as u16))?;
+            draw_scrollbar(stdout, view, row)?;
+        }
+    }
+    execute!(
+        stdout,
+        cursor::MoveTo(0, view.page_height as u16),
+        terminal::Clear(ClearType::CurrentLine)
+    )?;
+    draw_status(stdout, view)
+}
 
-    // Get terminal width for padding
-    let (term_width, _) = terminal::size().unwrap_or((80, 24));
-    let status_len = style::visible_len(&status) + style::visible_len(help);
-    let padding = if (term_width as usize) > status_len {
-        " ".repeat(term_width as usize - status_len)
-    } else {
-        String::new()
+/// Write screen row `row` of the page, highlighting the selected link.
+fn draw_row(stdout: &mut io::Stdout, view: &View, row: usize) -> io::Result<()> {
+    let index = view.offset + row;
+    let Some(line) = view.lines().get(index) else {
+        write!(stdout, "{}", filler_line(&view.opts))?;
+        return draw_scrollbar(stdout, view, row);
+    };
+    let mut line = match view.selected_link.and_then(|i| view.links().get(i)) {
+        Some(link) if link.line == index => {
+            style::insert_at_columns(line, link.start, link.end, style::REVERSE, style::NO_REVERSE)
+        }
+        _ => line.clone(),
     };
+    if view.line_numbers {
+        let gutter = gutter_width(view.total_lines());
+        let number = format!("{:>1$}", index + 1, gutter - 1);
+        write!(stdout, "{}{}{} ", style::DIM, number, style::RESET)?;
+        // The document was laid out for the full width, so cut what no longer fits
+        let room = view.term_width.saturating_sub(gutter + view.opts.scrollbar as usize);
+        if style::visible_len(&line) > room {
+            let glyphs = Glyphs::for_ascii(view.opts.ascii);
+            line = renderer::truncate_styled_at(&line, room, TruncatePosition::End, &glyphs, true);
+        }
+    }
+    write!(stdout, "{}", line)?;
+    draw_scrollbar(stdout, view, row)
+}
+
+/// Write the scrollbar cell for screen row `row` in the last column of the
+/// current line, if the scrollbar is on.
+fn draw_scrollbar(stdout: &mut io::Stdout, view: &View, row: usize) -> io::Result<()> {
+    if !view.opts.scrollbar {
+        return Ok(());
+    }
+    let thumb = scrollbar_thumb(view.offset, view.page_height, view.total_lines());
+    let glyph = match (thumb.contains(&row), view.opts.ascii) {
+        (true, false) => "█",
+        (true, true) => "#",
+        (false, false) => "│",
+        (false, true) => "|",
+    };
+    write!(
+        stdout,
+        "{}{}{}{}",
+        cursor::MoveToColumn(view.term_width.saturating_sub(1) as u16),
+        style::DIM,
+        glyph,
+        style::RESET
+    )
+}
+
+/// Write the status line, or the prompt being typed, at the cursor position.
+fn draw_status(stdout: &mut io::Stdout, view: &View) -> io::Result<()> {
+    if let Some(prompt) = &view.prompt {
+        write!(
+            stdout,
+            "{}{}{}",
+            prompt.label,
+            prompt.input,
+            terminal::Clear(ClearType::UntilNewLine)
+        )?;
+        stdout.flush()?;
+        return Ok(());
+    }
 
     write!(
         stdout,
-        "{}{}{}{}{}",
+        "{}{}{}",
         style::REVERSE,
-        status,
-        padding,
-        help,
+        status_line(view, view.term_width),
         style::RESET
     )?;
 
     stdout.flush()?;
     Ok(())
 }
+
+/// The status bar text, padded to `term_width`. A filename too long to fit is
+/// cut from the left so the position and help text stay visible.
+fn status_line(view: &View, term_width: usize) -> String {
+    let range = view.page();
+    let (offset, end) = (range.start, range.end);
+    let total_lines = view.total_lines();
+    let percentage = if total_lines == 0 {
+        100
+    } else {
+        ((end as f64 / total_lines as f64) * 100.0) as usize
+    };
+
+    let position = format!(" | lines {}-{} of {} ({}%) ", offset + 1, end, total_lines, percentage);
+
+    let help = match &view.message {
+        Some(message) => message.as_str(),
+        None => " [Space] next  [b] back  [q] quit ",
+    };
+
+    // One leading space, then whatever room is left goes to the filename
+    let fixed_len = 1 + style::visible_len(&position) + style::visible_len(help);
+    let room = term_width.saturating_sub(fixed_len);
+    let glyphs = Glyphs::for_ascii(view.opts.ascii);
+    let filename = if style::visible_len(view.filename) <= room {
+        view.filename.to_string()
+    } else if room > style::visible_len(glyphs.ellipsis) {
+        renderer::truncate_styled_at(view.filename, room, TruncatePosition::Start, &glyphs, false)
+    } else {
+        glyphs.ellipsis.to_string()
+    };
+
+    let status = format!(" {}{}", filename, position);
+    let status_len = style::visible_len(&status) + style::visible_len(help);
+    let padding = " ".repeat(term_width.saturating_sub(status_len));
+    let line = format!("{}{}{}", status, padding, help);
+    // Too narrow even without a filename: drop what doesn't fit
+    if status_len > term_width {
+        line.chars().take(term_width).collect()
+    } else {
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(line: usize) -> LinkSpan {
+        LinkSpan {
+            line,
+            start: 0,
+            end: 3,
+            url: format!("http://example.com/{}", line),
+        }
+    }
+
+    #[test]
+    fn test_next_link_after_selection() {
+        let links = vec![link(1), link(5), link(8), link(30)];
+        assert_eq!(next_link(&links, 0..10, None), Some(0));
+        assert_eq!(next_link(&links, 0..10, Some(0)), Some(1));
+        assert_eq!(next_link(&links, 0..10, Some(1)), Some(2));
+        // Wraps around to the first link on the page
+        assert_eq!(next_link(&links, 0..10, Some(2)), Some(0));
+        // Selection scrolled off the page restarts at the page's first link
+        assert_eq!(next_link(&links, 25..35, Some(1)), Some(3));
+        assert_eq!(next_link(&links, 10..20, None), None);
+    }
+
+    #[test]
+    fn test_prev_link_before_selection() {
+        let links = vec![link(1), link(5), link(8)];
+        assert_eq!(prev_link(&links, 0..10, None), Some(2));
+        assert_eq!(prev_link(&links, 0..10, Some(2)), Some(1));
+        assert_eq!(prev_link(&links, 0..10, Some(0)), Some(2));
+    }
+
+    #[test]
+    fn test_reload_offset_preserved_and_clamped() {
+        // Same position when the document is still long enough
+        assert_eq!(reload_offset(40, 200, 20), 40);
+        // Document shrank: clamp so the last page is full
+        assert_eq!(reload_offset(40, 50, 20), 30);
+        // Document now fits on one page
+        assert_eq!(reload_offset(40, 10, 20), 0);
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_prompt_accumulates_input() {
+        let mut prompt = Prompt::new("Save source to: ", PromptAction::SaveSource);
+        for c in "notes.mdx".chars() {
+            assert_eq!(prompt.handle_key(key(KeyCode::Char(c))), PromptEvent::Editing);
+        }
+        prompt.handle_key(key(KeyCode::Backspace));
+        assert_eq!(prompt.input, "notes.md");
+        // Control chords are not typed into the filename
+        prompt.handle_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL));
+        assert_eq!(prompt.input, "notes.md");
+        assert_eq!(
+            prompt.handle_key(key(KeyCode::Enter)),
+            PromptEvent::Submit("notes.md".to_string())
+        );
+    }
+
+    #[test]
+    fn test_prompt_cancel() {
+        let mut prompt = Prompt::new("Save source to: ", PromptAction::SaveSource);
+        assert_eq!(prompt.handle_key(key(KeyCode::Enter)), PromptEvent::Cancel);
+        prompt.handle_key(key(KeyCode::Char('x')));
+        prompt.handle_key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        assert_eq!(prompt.input, "");
+        prompt.handle_key(key(KeyCode::Char('x')));
+        assert_eq!(prompt.handle_key(key(KeyCode::Esc)), PromptEvent::Cancel);
+    }
+
+    #[test]
+    fn test_scroll_rows_for_single_line_scroll() {
+        // Down one line: only the bottom row is new
+        assert_eq!(scroll_rows(10, 11, 20), Some((1, 19..20)));
+        // Up one line: only the top row is new
+        assert_eq!(scroll_rows(11, 10, 20), Some((-1, 0..1)));
+        assert_eq!(scroll_rows(10, 13, 20), Some((3, 17..20)));
+        // No movement, or a whole page or more, needs a full redraw
+        assert_eq!(scroll_rows(10, 10, 20), None);
+        assert_eq!(scroll_rows(0, 20, 20), None);
+    }
+
+    #[test]
+    fn test_resize_recomputes_page_height_and_clamps_offset() {
+        let mut view = View {
+            doc: Rendered {
+                lines: vec![String::new(); 100],
+                links: Vec::new(),
+                code_blocks: Vec::new(),
+                footnotes: Vec::new(),
+                squeezed_tables: Vec::new(),
+            },
+            source: String::new(),
+            filename: "test.md",
+            term_width: 80,
+            page_height: 20,
+            offset: 75,
+            selected_link: None,
+            message: None,
+            prompt: None,
+            opts: PagerOptions::default(),
+            footnote_return: None,
+            line_numbers: false,
+        };
+        // Taller terminal: bigger pages, offset pulled back so the last page is full
+        view.resize(120, 41);
+        assert_eq!(view.term_width, 120);
+        assert_eq!(view.page_height, 40);
+        assert_eq!(view.offset, 60);
+        // Shorter terminal keeps the position
+        view.resize(80, 11);
+        assert_eq!(view.page_height, 10);
+        assert_eq!(view.offset, 60);
+        // Taller than the document: everything fits from the top
+        view.resize(80, 200);
+        assert_eq!(view.offset, 0);
+        // Never less than one content row
+        view.resize(80, 1);
+        assert_eq!(view.page_height, 1);
+    }
+
+    #[test]
+    fn test_short_watched_document_scrolls_after_shrinking() {
+        assert!(prints_directly(10, 20, false));
+        assert!(!prints_directly(30, 20, false));
+        // Watched documents that fit still get a session
+        assert!(!prints_directly(10, 20, true));
+        let mut view = View {
+            doc: Rendered {
+                lines: vec![String::new(); 10],
+                ..Rendered::default()
+            },
+            source: String::new(),
+            filename: "test.md",
+            term_width: 80,
+            page_height: 20,
+            offset: 0,
+            selected_link: None,
+            message: None,
+            prompt: None,
+            opts: PagerOptions::default(),
+            footnote_return: None,
+            line_numbers: false,
+        };
+        assert_eq!(view.max_offset(), 0);
+        // The terminal shrinks below the document: it now pages
+        view.resize(80, 5);
+        assert_eq!(view.page_height, 4);
+        assert_eq!(view.max_offset(), 6);
+        assert_eq!(view.page(), 0..4);
+    }
+
+    #[test]
+    fn test_save_asks_before_overwriting() {
+        let dir = std::env::temp_dir().join(format!("markterm-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let existing = dir.join("existing.md");
+        fs::write(&existing, "keep me").unwrap();
+        let existing = existing.to_str().unwrap().to_string();
+        let mut view = View {
+            doc: Rendered::default(),
+            source: "# New".to_string(),
+            filename: "test.md",
+            term_width: 80,
+            page_height: 20,
+            offset: 0,
+            selected_link: None,
+            message: None,
+            prompt: None,
+            opts: PagerOptions::default(),
+            footnote_return: None,
+            line_numbers: false,
+        };
+        let save = || Prompt::new("Save source to: ", PromptAction::SaveSource);
+
+        // An existing file isn't touched until the overwrite is confirmed
+        view.submit(save(), existing.clone());
+        let confirm = view.prompt.take().expect("overwrite prompt");
+        assert_eq!(confirm.overwrite.as_deref(), Some(existing.as_str()));
+        view.submit(confirm, "n".to_string());
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "keep me");
+
+        view.submit(save(), existing.clone());
+        let confirm = view.prompt.take().expect("overwrite prompt");
+        view.submit(confirm, "y".to_string());
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "# New");
+
+        // A new file is written straight away
+        let new = dir.join("new.md").to_str().unwrap().to_string();
+        view.submit(save(), new.clone());
+        assert!(view.prompt.is_none());
+        assert_eq!(fs::read_to_string(&new).unwrap(), "# New");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_replace_drops_link_selection() {
+        let link = |url: &str| LinkSpan { line: 0, start: 0, end: 4, url: url.to_string() };
+        let doc = |url: &str| Rendered {
+            lines: vec!["link".to_string()],
+            links: vec![link(url)],
+            ..Rendered::default()
+        };
+        let mut view = View {
+            doc: doc("http://old.example"),
+            source: String::new(),
+            filename: "test.md",
+            term_width: 80,
+            page_height: 20,
+            offset: 0,
+            selected_link: Some(0),
+            message: Some(" http://old.example ".to_string()),
+            prompt: None,
+            opts: PagerOptions::default(),
+            footnote_return: None,
+            line_numbers: false,
+        };
+        view.replace(String::new(), doc("http://new.example"));
+        // Enter can't act on a link picked in the old document
+        assert_eq!(view.selected_link, None);
+        assert_eq!(view.message, None);
+    }
+
+    #[test]
+    fn test_status_line_truncates_long_filename_from_left() {
+        let filename = format!("/home/user/{}/notes/chapter-one.md", "deeply/nested".repeat(10));
+        let view = View {
+            doc: Rendered {
+                lines: vec![String::new(); 100],
+                links: Vec::new(),
+                code_blocks: Vec::new(),
+                footnotes: Vec::new(),
+                squeezed_tables: Vec::new(),
+            },
+            source: String::new(),
+            filename: &filename,
+            term_width: 80,
+            page_height: 20,
+            offset: 0,
+            selected_link: None,
+            message: None,
+            prompt: None,
+            opts: PagerOptions::default(),
+            footnote_return: None,
+            line_numbers: false,
+        };
+        let line = status_line(&view, 100);
+        assert_eq!(style::visible_len(&line), 100, "Got: {:?}", line);
+        assert!(line.starts_with(" …"), "Got: {:?}", line);
+        assert!(line.contains("notes/chapter-one.md | lines 1-20 of 100"), "Got: {:?}", line);
+        assert!(line.ends_with("[q] quit "), "Got: {:?}", line);
+        // Short names are untouched and padded to the width
+        let view = View { filename: "a.md", ..view };
+        let line = status_line(&view, 80);
+        assert!(line.starts_with(" a.md | lines"));
+        assert_eq!(style::visible_len(&line), 80);
+    }
+
+    #[test]
+    fn test_restore_guard_runs_on_drop() {
+        use std::cell::Cell;
+        use std::panic::{self, AssertUnwindSafe};
+
+        let restored = Cell::new(0);
+        {
+            let _guard = RestoreGuard::new(|| restored.set(restored.get() + 1));
+            assert_eq!(restored.get(), 0);
+        }
+        assert_eq!(restored.get(), 1);
+
+        // Also while unwinding from a panic
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = RestoreGuard::new(|| restored.set(restored.get() + 1));
+            panic!("boom");
+        }));
+        assert!(result.is_err());
+        assert_eq!(restored.get(), 2);
+    }
+
+    #[test]
+    fn test_only_safe_schemes_are_opened() {
+        assert!(scheme_allowed("https://example.com", OPENABLE_SCHEMES));
+        assert!(scheme_allowed("HTTP://example.com", OPENABLE_SCHEMES));
+        assert!(!scheme_allowed("file:///usr/bin/xterm", OPENABLE_SCHEMES));
+        assert!(!scheme_allowed("file:///etc", &["http", "https"]));
+        assert!(!scheme_allowed("javascript:alert(1)", OPENABLE_SCHEMES));
+        assert!(!scheme_allowed("docs/guide.md", OPENABLE_SCHEMES));
+    }
+
+    #[test]
+    fn test_filler_lines_by_mode() {
+        let mut opts = PagerOptions::default();
+        assert_eq!(filler_line(&opts), "~");
+        opts.filler_style = style::DIM.to_string();
+        assert_eq!(filler_line(&opts), format!("{}~{}", style::DIM, style::RESET));
+        opts.empty_lines = "blank".parse().unwrap();
+        assert_eq!(filler_line(&opts), "");
+        assert!("dots".parse::<EmptyLines>().is_err());
+    }
+
+    #[test]
+    fn test_footnote_target_finds_definition() {
+        let input = "Intro.\n\nFirst[^a] and second[^b].\n\nMore.\n\n[^a]: Note A.\n\n[^b]: Note B.\n";
+        let doc = renderer::render_document(
+            crate::parser::parse(input),
+            40,
+            &renderer::RenderOptions::default(),
+        );
+        let def = footnote_target(&doc.footnotes, 0..4).unwrap();
+        assert!(doc.lines[def].contains("Note A."), "{:?}", doc.lines);
+        let b = doc.footnotes.iter().find(|n| n.label == "b").unwrap();
+        assert!(doc.lines[b.definition.unwrap()].contains("Note B."));
+        let second = doc.lines.iter().position(|l| l.contains("second")).unwrap();
+        assert_eq!(b.refs, [second]);
+        // No reference on the page, nowhere to jump
+        assert_eq!(footnote_target(&doc.footnotes, 4..6), None);
+    }
+
+    #[test]
+    fn test_scrollbar_thumb() {
+        // Everything fits: the thumb fills the track
+        assert_eq!(scrollbar_thumb(0, 10, 5), 0..10);
+        // A quarter of the document is visible
+        assert_eq!(scrollbar_thumb(0, 10, 40), 0..3);
+        assert_eq!(scrollbar_thumb(15, 10, 40), 4..7);
+        assert_eq!(scrollbar_thumb(30, 10, 40), 7..10);
+        // Huge documents still get a one-row thumb that reaches the bottom
+        assert_eq!(scrollbar_thumb(0, 10, 100_000), 0..1);
+        assert_eq!(scrollbar_thumb(99_990, 10, 100_000), 9..10);
+    }
+
+    #[test]
+    fn test_gutter_width() {
+        assert_eq!(gutter_width(0), 2);
+        assert_eq!(gutter_width(9), 2);
+        assert_eq!(gutter_width(10), 3);
+        assert_eq!(gutter_width(999), 4);
+        assert_eq!(gutter_width(1000), 5);
+    }
+}
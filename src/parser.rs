@@ -1,13 +1,88 @@
-/// Markdown parsing wrapper around pulldown-cmark.
+//! Markdown parsing wrapper around pulldown-cmark.
 
-use pulldown_cmark::{Event, Options, Parser};
+use pulldown_cmark::{BrokenLink, CowStr, Event, Options, Parser, Tag, TagEnd, TextMergeStream};
 
-/// Parse markdown content and return an owned vector of events.
-pub fn parse(content: &str) -> Vec<Event<'_>> {
+fn options() -> Options {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
     options.insert(Options::ENABLE_TABLES);
     options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_MATH);
+    options
+}
+
+/// Parse markdown content and return an owned vector of events.
+/// Adjacent text events are merged, so a run of prose arrives as one `Text`.
+pub fn parse(content: &str) -> Vec<Event<'_>> {
+    parse_iter(content).collect()
+}
+
+/// Title given to an image whose reference (`![alt][ref]`) has no
+/// definition. Its `dest_url` is the missing reference label.
+pub const UNRESOLVED_IMAGE: &str = "\0unresolved";
+
+/// Like [`parse`], but yields events lazily as the source is read.
+pub fn parse_iter(content: &str) -> impl Iterator<Item = Event<'_>> {
+    // Unresolved image references still become images, so they can be flagged;
+    // a bracketed word that isn't a link stays plain text
+    let unresolved_image = move |link: BrokenLink<'_>| {
+        content[link.span.clone()]
+            .starts_with('!')
+            .then(|| (CowStr::from(link.reference.into_string()), CowStr::from(UNRESOLVED_IMAGE)))
+    };
+    TextMergeStream::new(Parser::new_with_broken_link_callback(
+        content,
+        options(),
+        Some(unresolved_image),
+    ))
+}
+
+/// A heading found in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// 1 to 6
+    pub level: usize,
+    pub text: String,
+    /// 1-based source line the heading starts on
+    pub line: usize,
+}
+
+/// The document's headings in order, without rendering anything else.
+pub fn headings(content: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut current: Option<Heading> = None;
+    for (event, range) in Parser::new_ext(content, options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                let line = content[..range.start].matches('\n').count() + 1;
+                current = Some(Heading { level: level as usize, text: String::new(), line });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = current.as_mut() {
+                    heading.text.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => headings.extend(current.take()),
+            _ => {}
+        }
+    }
+    headings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Parser::new_ext(content, options).collect()
+    #[test]
+    fn test_headings_with_source_lines() {
+        let headings = headings("# Title\n\ntext\n\nSub `code`\n---\n");
+        assert_eq!(
+            headings,
+            [
+                Heading { level: 1, text: "Title".into(), line: 1 },
+                Heading { level: 2, text: "Sub code".into(), line: 5 },
+            ]
+        );
+    }
 }
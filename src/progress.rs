@@ -0,0 +1,85 @@
+//! A spinner on stderr while slow input (a large file, a slow pipe) is read.
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long reading may take before the spinner appears, so fast reads
+/// never flash it.
+pub const DELAY: Duration = Duration::from_millis(300);
+
+const FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+const TICK: Duration = Duration::from_millis(100);
+
+/// Whether the spinner should be drawn: only on a terminal, and only once
+/// reading has taken longer than [`DELAY`].
+pub fn should_show(stderr_is_tty: bool, elapsed: Duration) -> bool {
+    stderr_is_tty && elapsed >= DELAY
+}
+
+/// A running spinner, stopped and erased by [`Spinner::finish`].
+pub struct Spinner {
+    done: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    /// Start a spinner labelled `label`. Nothing is drawn unless stderr is a
+    /// terminal and the spinner is still running after [`DELAY`].
+    pub fn start(label: &str) -> Spinner {
+        let done = Arc::new(AtomicBool::new(false));
+        let is_tty = io::stderr().is_terminal();
+        let thread = is_tty.then(|| {
+            let done = Arc::clone(&done);
+            let label = label.to_string();
+            thread::spawn(move || spin(&done, &label))
+        });
+        Spinner { done, thread }
+    }
+
+    /// Stop the spinner and clear its line, before any output is drawn.
+    pub fn finish(mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            // Wake it now rather than at its next tick
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+fn spin(done: &AtomicBool, label: &str) {
+    let start = Instant::now();
+    let mut shown = false;
+    let mut stderr = io::stderr();
+    for frame in FRAMES.iter().cycle() {
+        thread::park_timeout(TICK);
+        if done.load(Ordering::Relaxed) {
+            break;
+        }
+        if should_show(true, start.elapsed()) {
+            let _ = write!(stderr, "\r{} {}", frame, label);
+            let _ = stderr.flush();
+            shown = true;
+        }
+    }
+    if shown {
+        let _ = write!(stderr, "\r\x1b[K");
+        let _ = stderr.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_show_after_delay_on_tty() {
+        assert!(!should_show(true, Duration::ZERO));
+        assert!(!should_show(true, DELAY - Duration::from_millis(1)));
+        assert!(should_show(true, DELAY));
+        assert!(!should_show(false, Duration::from_secs(10)));
+    }
+}
@@ -1,28 +1,621 @@
-/// Converts pulldown-cmark events into ANSI-styled, word-wrapped terminal lines.
+//! Converts pulldown-cmark events into ANSI-styled, word-wrapped terminal lines.
 
 use pulldown_cmark::{Event, Tag, TagEnd, CodeBlockKind};
 
-use crate::style;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::grep;
+use crate::highlight::{self, Language};
+use crate::math;
+use crate::parser;
+use crate::style::{self, Theme};
+use crate::terminal::ImageProtocol;
+
+/// Border style used when drawing tables.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TableStyle {
+    /// Full box-drawing borders around every cell.
+    #[default]
+    Grid,
+    /// Header underline only, no vertical bars (like pandoc simple tables).
+    Simple,
+    /// Aligned columns with no borders at all.
+    Minimal,
+}
+
+impl FromStr for TableStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grid" => Ok(TableStyle::Grid),
+            "simple" => Ok(TableStyle::Simple),
+            "minimal" => Ok(TableStyle::Minimal),
+            _ => Err(format!("unknown table style '{}' (expected grid, simple, minimal)", s)),
+        }
+    }
+}
+
+/// How headings stand out besides their color.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeadingStyle {
+    /// Color and weight only.
+    #[default]
+    Color,
+    /// H1 and H2 are also underlined with a full-width rule, like setext headings.
+    Underline,
+}
+
+impl FromStr for HeadingStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "color" => Ok(HeadingStyle::Color),
+            "underline" => Ok(HeadingStyle::Underline),
+            _ => Err(format!("unknown heading style '{}' (expected color, underline)", s)),
+        }
+    }
+}
+
+/// Where the ellipsis goes when a line is truncated in no-wrap mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TruncatePosition {
+    /// Keep the end of the line: `…/path/file.md`
+    Start,
+    /// Keep both ends: `/very/long/…/file.md`
+    Middle,
+    /// Keep the start of the line: `/very/long/pa…`
+    #[default]
+    End,
+}
+
+impl FromStr for TruncatePosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(TruncatePosition::Start),
+            "middle" => Ok(TruncatePosition::Middle),
+            "end" => Ok(TruncatePosition::End),
+            _ => Err(format!("unknown truncate position '{}' (expected start, middle, end)", s)),
+        }
+    }
+}
+
+/// How ordered list item numbers are shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OrderedStyle {
+    /// `1.`, `2.`, `3.`
+    #[default]
+    Decimal,
+    /// `a.`, `b.`, `c.`
+    Alpha,
+    /// `i.`, `ii.`, `iii.`
+    Roman,
+    /// `1.`, `1.1.`, `1.1.1.`: nested items carry the numbers of their
+    /// ordered parents
+    Legal,
+}
+
+impl FromStr for OrderedStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "decimal" => Ok(OrderedStyle::Decimal),
+            "alpha" => Ok(OrderedStyle::Alpha),
+            "roman" => Ok(OrderedStyle::Roman),
+            "legal" => Ok(OrderedStyle::Legal),
+            _ => Err(format!(
+                "unknown ordered style '{}' (expected decimal, alpha, roman, legal)",
+                s
+            )),
+        }
+    }
+}
+
+impl OrderedStyle {
+    /// The label for item number `n`. Numbers with no letter or roman form
+    /// (zero, or roman above 3999) fall back to decimal.
+    fn label(self, n: u64) -> String {
+        match self {
+            OrderedStyle::Alpha if n > 0 => to_alpha(n),
+            OrderedStyle::Roman if (1..4000).contains(&n) => to_roman(n),
+            _ => n.to_string(),
+        }
+    }
+}
+
+/// The full number of the innermost ordered item, like `2.1.3.`: each open
+/// ordered list contributes its current item, bulleted lists are skipped.
+fn legal_number(list_stack: &[ListContext]) -> String {
+    let last = list_stack.len().saturating_sub(1);
+    list_stack
+        .iter()
+        .enumerate()
+        .filter_map(|(i, context)| match context {
+            // Parents have already moved on to their next number
+            ListContext::Ordered(next) if i < last => Some(next.saturating_sub(1)),
+            ListContext::Ordered(current) => Some(*current),
+            ListContext::Unordered(_) => None,
+        })
+        .map(|n| format!("{}.", n))
+        .collect()
+}
+
+/// Bijective base-26: 1 → a, 26 → z, 27 → aa.
+fn to_alpha(mut n: u64) -> String {
+    let mut letters = Vec::new();
+    while n > 0 {
+        n -= 1;
+        letters.push((b'a' + (n % 26) as u8) as char);
+        n /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
+/// Lowercase roman numerals for 1..=3999.
+fn to_roman(mut n: u64) -> String {
+    const NUMERALS: &[(u64, &str)] = &[
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+        (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+    let mut out = String::new();
+    for &(value, numeral) in NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+/// How inline code is set apart from the text around it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InlineCodeStyle {
+    /// A grey background
+    #[default]
+    Bg,
+    /// Between `‹` and `›` (`[` and `]` with `--ascii`), for terminals
+    /// without 256-color backgrounds
+    Brackets,
+    /// Underlined, for terminals without background colors
+    Underline,
+}
+
+impl FromStr for InlineCodeStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bg" => Ok(InlineCodeStyle::Bg),
+            "brackets" => Ok(InlineCodeStyle::Brackets),
+            "underline" => Ok(InlineCodeStyle::Underline),
+            _ => Err(format!("unknown inline code style '{}' (expected bg, brackets, underline)", s)),
+        }
+    }
+}
+
+/// Options controlling how markdown is rendered.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    pub use_color: bool,
+    pub no_wrap: bool,
+    pub truncate: TruncatePosition,
+    pub table_style: TableStyle,
+    pub heading_style: HeadingStyle,
+    pub inline_code_style: InlineCodeStyle,
+    /// Fence languages whose blocks are emitted verbatim, without border or wrapping.
+    pub raw_fences: Vec<String>,
+    /// Number of blank lines between blocks.
+    pub block_spacing: usize,
+    /// Replace ASCII arrows and operators in prose with Unicode symbols.
+    pub symbols: bool,
+    /// Without color, show emphasis as `*bold*`, `_italic_`, `~~strike~~`.
+    pub keep_markers: bool,
+    /// Tab stop spacing used when expanding tabs in prose.
+    pub tab_width: usize,
+    /// Number links as `text[N]` and list their URLs in a footer.
+    pub link_refs: bool,
+    pub ordered_style: OrderedStyle,
+    /// Show a one-line paragraph directly before a table as its centered caption.
+    pub table_captions: bool,
+    /// Repeat a table's header after every N data rows (0 = never).
+    pub table_header_repeat: usize,
+    /// Render `<!-- ... -->` comments as dim italic notes instead of hiding them.
+    pub show_comments: bool,
+    /// Replace box drawing, bullets and other non-ASCII output with ASCII.
+    pub ascii: bool,
+    /// Italicize the first paragraph after the first `#` heading as a lead.
+    pub lead_style: bool,
+    /// Append a footer with word count, reading time and heading count.
+    pub stats: bool,
+    /// Graphics protocol for inline images. Images currently always render
+    /// as an `[image: alt]` placeholder.
+    pub image_protocol: ImageProtocol,
+    /// Colors used when `use_color` is set.
+    pub theme: Theme,
+    /// Bullets for each level of unordered list; the last one repeats for
+    /// deeper levels.
+    pub bullets: Vec<String>,
+    /// In `diff` code blocks, highlight the words that changed between a
+    /// removed line and the added line replacing it.
+    pub word_diff: bool,
+    /// Indent all but the first line of each paragraph by this many columns.
+    pub hanging_indent: usize,
+    /// Wrap prose at this many columns while code blocks and tables may
+    /// still take the full width; 0 wraps everything at the full width.
+    pub prose_width: usize,
+    /// Cut each paragraph off after this many wrapped lines, ending the last
+    /// with an ellipsis; 0 leaves paragraphs whole.
+    pub max_paragraph_lines: usize,
+    /// After each top-level list with task items, show how many are checked.
+    pub task_summary: bool,
+    /// Render only the blocks containing this text (case-insensitive),
+    /// under their headings.
+    pub grep: Option<String>,
+    /// Leave out tables entirely.
+    pub no_tables: bool,
+    /// Leave out code blocks entirely; inline code is kept.
+    pub no_code: bool,
+    /// Leave out images entirely, placeholders included.
+    pub no_images: bool,
+    /// Lay out lists whose items all read `**Term** — definition` as two
+    /// columns, definitions lined up after the longest term.
+    pub glossary: bool,
+}
+
+/// Fence languages that are always rendered verbatim.
+pub const DEFAULT_RAW_FENCES: &[&str] = &["raw", "nowrap"];
+
+/// Unordered list bullets by nesting level.
+pub const DEFAULT_BULLETS: &[&str] = &Glyphs::UNICODE.bullets;
+
+/// The decorative glyphs the renderer draws, in one place so `--ascii` can
+/// swap in ASCII stand-ins for all of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Glyphs {
+    /// Default unordered list bullets by nesting level.
+    pub bullets: [&'static str; 3],
+    /// Bullet for lists nested past the indent cap.
+    pub deep_bullet: &'static str,
+    /// Horizontal rules and box edges.
+    pub rule: &'static str,
+    /// Underline for H1 in the underline heading style (H2 uses `rule`).
+    pub double_rule: &'static str,
+    /// Block quote, code block and table borders.
+    pub bar: &'static str,
+    pub code_top: &'static str,
+    pub code_bottom: &'static str,
+    /// Table corners and junctions for the top, middle and bottom borders,
+    /// each as left, middle, right.
+    pub table: [[&'static str; 3]; 3],
+    /// Checked task list box contents.
+    pub check: &'static str,
+    /// Around inline code in the `brackets` inline code style.
+    pub code_brackets: [&'static str; 2],
+    /// Marks cut-off text.
+    pub ellipsis: &'static str,
+}
+
+impl Glyphs {
+    pub const UNICODE: Glyphs = Glyphs {
+        bullets: ["•", "◦", "▪"],
+        deep_bullet: "▸",
+        rule: "─",
+        double_rule: "═",
+        bar: "│",
+        code_top: "╭",
+        code_bottom: "╰",
+        table: [["┌", "┬", "┐"], ["├", "┼", "┤"], ["└", "┴", "┘"]],
+        check: "✓",
+        code_brackets: ["‹", "›"],
+        ellipsis: "…",
+    };
+
+    pub const ASCII: Glyphs = Glyphs {
+        bullets: ["*", "o", "-"],
+        deep_bullet: ">",
+        rule: "-",
+        double_rule: "=",
+        bar: "|",
+        code_top: "+",
+        code_bottom: "+",
+        table: [["+"; 3]; 3],
+        check: "x",
+        code_brackets: ["[", "]"],
+        ellipsis: "...",
+    };
+
+    /// The ASCII glyphs if `ascii` is set, otherwise the Unicode ones.
+    pub fn for_ascii(ascii: bool) -> Glyphs {
+        if ascii {
+            Glyphs::ASCII
+        } else {
+            Glyphs::UNICODE
+        }
+    }
+
+    /// Every glyph in a fixed order, so two sets line up pair by pair.
+    fn all(&self) -> Vec<&'static str> {
+        let mut all = vec![
+            self.deep_bullet,
+            self.rule,
+            self.double_rule,
+            self.bar,
+            self.code_top,
+            self.code_bottom,
+            self.check,
+            self.ellipsis,
+        ];
+        all.extend(self.bullets);
+        all.extend(self.code_brackets);
+        all.extend(self.table.into_iter().flatten());
+        all
+    }
+}
+
+/// Fence languages holding terminal output whose color codes are kept.
+pub const ANSI_FENCES: &[&str] = &["ansi", "console"];
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            use_color: true,
+            no_wrap: false,
+            truncate: TruncatePosition::End,
+            table_style: TableStyle::Grid,
+            heading_style: HeadingStyle::Color,
+            inline_code_style: InlineCodeStyle::Bg,
+            raw_fences: DEFAULT_RAW_FENCES.iter().map(|s| s.to_string()).collect(),
+            block_spacing: 1,
+            symbols: false,
+            keep_markers: false,
+            tab_width: 4,
+            link_refs: false,
+            ordered_style: OrderedStyle::Decimal,
+            table_captions: false,
+            table_header_repeat: 0,
+            show_comments: false,
+            ascii: false,
+            image_protocol: ImageProtocol::None,
+            stats: false,
+            lead_style: false,
+            theme: Theme::default(),
+            bullets: DEFAULT_BULLETS.iter().map(|s| s.to_string()).collect(),
+            grep: None,
+            word_diff: false,
+            task_summary: false,
+            hanging_indent: 0,
+            prose_width: 0,
+            max_paragraph_lines: 0,
+            no_tables: false,
+            no_code: false,
+            no_images: false,
+            glossary: false,
+        }
+    }
+}
+
+/// A fenced code block's info string, split into language and attributes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FenceInfo {
+    /// The language token, e.g. `rust` for ```` ```rust,ignore ````.
+    pub lang: String,
+    /// Remaining attributes, e.g. `["ignore"]` or `[".line-numbers"]`.
+    pub attrs: Vec<String>,
+}
+
+impl FenceInfo {
+    /// Parse an info string such as `rust,no_run` or `rust {.line-numbers}`.
+    pub fn parse(info: &str) -> Self {
+        let info = info.trim();
+        let split = info
+            .find(|c: char| c == ',' || c == '{' || c.is_whitespace())
+            .unwrap_or(info.len());
+        let (lang, rest) = info.split_at(split);
+        let attrs = rest
+            .split(|c: char| c == ',' || c == '{' || c == '}' || c.is_whitespace())
+            .filter(|a| !a.is_empty())
+            .map(str::to_string)
+            .collect();
+        Self {
+            lang: lang.to_string(),
+            attrs,
+        }
+    }
+
+    /// Whether the block carries `attr`, with or without a leading `.`.
+    pub fn has_attr(&self, attr: &str) -> bool {
+        self.attrs.iter().any(|a| a.trim_start_matches('.') == attr)
+    }
+}
+
+/// Position of a link's URL in the rendered output, in visible columns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinkSpan {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+    pub url: String,
+}
+
+/// Where a footnote is referenced and defined in the rendered output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FootnoteSpan {
+    pub label: String,
+    /// Lines with a `[label]` reference, in order.
+    pub refs: Vec<usize>,
+    /// First line of the definition, if the document has one.
+    pub definition: Option<usize>,
+}
+
+/// A code block's original text and where it was drawn.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// Fence language tag, empty for indented or untagged blocks.
+    pub lang: String,
+    /// The code exactly as written, without borders or highlighting.
+    pub source: String,
+    /// Rendered lines occupied by the block, including its borders.
+    pub lines: Range<usize>,
+}
+
+/// Rendered lines plus metadata the pager uses for navigation.
+#[derive(Clone, Debug, Default)]
+pub struct Rendered {
+    pub lines: Vec<String>,
+    pub links: Vec<LinkSpan>,
+    pub code_blocks: Vec<CodeBlock>,
+    pub footnotes: Vec<FootnoteSpan>,
+    /// Tables too wide for the width, whose columns were narrowed to fit.
+    pub squeezed_tables: Vec<TableFit>,
+}
+
+/// A table whose natural width exceeds the render width.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableFit {
+    /// Rendered line the table starts on.
+    pub line: usize,
+    /// Width with every column as wide as its widest cell, borders included.
+    pub natural: usize,
+    /// Width after narrowing columns (cells wrap to fit).
+    pub fitted: usize,
+}
+
+impl TableFit {
+    /// How the table was fitted, e.g. `is 60 columns wide, narrowed to 30 (50%)`.
+    /// Columns already at their minimum width can't be narrowed at all.
+    pub fn describe(&self) -> String {
+        if self.fitted >= self.natural {
+            format!("is {} columns wide and can't be narrowed", self.natural)
+        } else {
+            format!(
+                "is {} columns wide, narrowed to {} ({}%)",
+                self.natural,
+                self.fitted,
+                self.fitted * 100 / self.natural
+            )
+        }
+    }
+}
+
+/// Progress towards the lead paragraph of `--lead-style`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Lead {
+    /// No `#` heading seen yet
+    BeforeTitle,
+    /// Title seen; the next top-level paragraph is the lead
+    Pending,
+    /// Rendering the lead paragraph
+    Active,
+    /// Lead rendered, or the option is off
+    Done,
+}
 
 struct RenderState {
     use_color: bool,
     no_wrap: bool,
+    truncate: TruncatePosition,
+    table_style: TableStyle,
+    heading_style: HeadingStyle,
+    inline_code_style: InlineCodeStyle,
+    raw_fences: Vec<String>,
+    block_spacing: usize,
+    symbols: bool,
+    keep_markers: bool,
+    tab_width: usize,
+    link_refs: bool,
+    ordered_style: OrderedStyle,
+    table_captions: bool,
+    table_header_repeat: usize,
+    show_comments: bool,
+    ascii: bool,
+    lead: Lead,
+    theme: Theme,
+    glyphs: Glyphs,
+    bullets: Vec<String>,
+    word_diff: bool,
+    task_summary: bool,
+    /// Checked and total task items in the current top-level list
+    tasks: (usize, usize),
     width: usize,
+    /// Wrap width for prose, headings and rules; at most `width`
+    text_width: usize,
     lines: Vec<String>,
     current_line: String,
     indent: usize,
-    bold: bool,
-    italic: bool,
-    strikethrough: bool,
+    /// Extra indent for all but the first line of a paragraph
+    hanging_indent: usize,
+    in_paragraph: bool,
+    /// No line of the current paragraph has been flushed yet
+    paragraph_first: bool,
+    max_paragraph_lines: usize,
+    /// The current paragraph has hit `max_paragraph_lines` and been cut off
+    paragraph_clamped: bool,
+    /// Width of the current list item's marker: wrapped lines and later
+    /// paragraphs of the item are indented this much more, under its text
+    hang: usize,
+    /// The item's marker is at the start of `current_line` and not yet flushed
+    marker_pending: bool,
+    /// Indent of each open list and the hang of the item it's nested in,
+    /// innermost last
+    list_indents: Vec<(usize, usize)>,
+    /// Term column width of each list not yet started that gets a glossary
+    /// layout, or `None` for a list that doesn't
+    glossary: VecDeque<Option<usize>>,
+    /// Term column width of each open list, innermost last
+    glossary_lists: Vec<Option<usize>>,
+    /// Term column width while the current item's term is being read
+    glossary_term: Option<usize>,
+    /// The separator before a definition is still to be dropped
+    glossary_dash: bool,
+    /// Marker and padded term, drawn before the first line of the definition
+    line_lead: Option<String>,
+    /// Checkbox standing in for a task item's bullet, hung into the indent
+    task_box: Option<String>,
+    /// Reference label of the open image, when it has no definition
+    missing_image: Option<String>,
+    /// Nesting depth of each emphasis style; applied while above zero
+    bold: usize,
+    italic: usize,
+    strikethrough: usize,
     in_code_block: bool,
-    in_blockquote: bool,
+    in_raw_block: bool,
+    in_ansi_block: bool,
+    code_lang: Option<&'static Language>,
+    /// Column each open quote's bar is drawn at (0 for the page margin) and
+    /// the item hang it suspends, innermost last
+    quotes: Vec<(usize, usize)>,
+    /// Item marker drawn over the start of the next line's prefix, when the
+    /// item opens with a quote rather than a paragraph
+    marker_lead: Option<String>,
     in_heading: Option<u8>,
     list_stack: Vec<ListContext>,
     link_url: Option<String>,
+    link_urls: Vec<String>,
+    footnote_label: Option<String>,
+    /// Each footnote reference's label and the first line it can be on
+    footnote_refs: Vec<(String, usize)>,
+    /// Each footnote definition's label and first line
+    footnote_defs: Vec<(String, usize)>,
+    squeezed_tables: Vec<TableFit>,
     table_row: Vec<String>,
     table_cell_buf: String,
     table_alignments: Vec<pulldown_cmark::Alignment>,
     table_rows: Vec<Vec<String>>,
+    table_caption: Option<String>,
+    /// Raw text of the HTML block being read, if any
+    html_block: Option<String>,
+    code_block: Option<CodeBlock>,
+    code_blocks: Vec<CodeBlock>,
+    /// Start line of the paragraph being rendered, then its finished line
+    /// range until the next block starts.
+    paragraph_start: usize,
+    last_paragraph: Option<(usize, usize)>,
     in_table_head: bool,
     in_table_cell: bool,
 }
@@ -34,28 +627,107 @@ enum ListContext {
 }
 
 impl RenderState {
-    fn new(width: u16, use_color: bool, no_wrap: bool) -> Self {
+    fn new(width: u16, opts: &RenderOptions) -> Self {
         Self {
-            use_color,
-            no_wrap,
+            use_color: opts.use_color,
+            no_wrap: opts.no_wrap,
+            truncate: opts.truncate,
+            table_style: opts.table_style,
+            heading_style: opts.heading_style,
+            inline_code_style: opts.inline_code_style,
+            raw_fences: opts.raw_fences.clone(),
+            block_spacing: opts.block_spacing,
+            symbols: opts.symbols,
+            keep_markers: opts.keep_markers,
+            tab_width: opts.tab_width,
+            link_refs: opts.link_refs,
+            ordered_style: opts.ordered_style,
+            table_captions: opts.table_captions,
+            table_header_repeat: opts.table_header_repeat,
+            show_comments: opts.show_comments,
+            ascii: opts.ascii,
+            lead: if opts.lead_style { Lead::BeforeTitle } else { Lead::Done },
+            theme: opts.theme.clone(),
+            glyphs: Glyphs::for_ascii(opts.ascii),
+            // The default bullets follow the glyph set; custom ones are kept
+            bullets: if opts.ascii && opts.bullets == DEFAULT_BULLETS {
+                Glyphs::ASCII.bullets.map(String::from).to_vec()
+            } else {
+                opts.bullets.clone()
+            },
+            word_diff: opts.word_diff,
+            task_summary: opts.task_summary,
+            tasks: (0, 0),
             width: width.saturating_sub(2) as usize, // margin
+            text_width: match opts.prose_width {
+                0 => width,
+                n => width.min(n.min(u16::MAX as usize) as u16),
+            }
+            .saturating_sub(2) as usize,
             lines: Vec::new(),
             current_line: String::new(),
             indent: 0,
-            bold: false,
-            italic: false,
-            strikethrough: false,
+            hanging_indent: opts.hanging_indent,
+            in_paragraph: false,
+            paragraph_first: false,
+            max_paragraph_lines: opts.max_paragraph_lines,
+            paragraph_clamped: false,
+            hang: 0,
+            marker_pending: false,
+            list_indents: Vec::new(),
+            bold: 0,
+            italic: 0,
+            strikethrough: 0,
             in_code_block: false,
-            in_blockquote: false,
+            in_raw_block: false,
+            in_ansi_block: false,
+            code_lang: None,
+            quotes: Vec::new(),
+            marker_lead: None,
             in_heading: None,
             list_stack: Vec::new(),
             link_url: None,
+            link_urls: Vec::new(),
+            footnote_label: None,
+            footnote_refs: Vec::new(),
+            footnote_defs: Vec::new(),
+            squeezed_tables: Vec::new(),
             table_row: Vec::new(),
             table_cell_buf: String::new(),
             table_alignments: Vec::new(),
             table_rows: Vec::new(),
+            table_caption: None,
+            html_block: None,
+            code_block: None,
+            code_blocks: Vec::new(),
+            paragraph_start: 0,
+            last_paragraph: None,
             in_table_head: false,
             in_table_cell: false,
+            glossary: VecDeque::new(),
+            glossary_lists: Vec::new(),
+            glossary_term: None,
+            glossary_dash: false,
+            line_lead: None,
+            task_box: None,
+            missing_image: None,
+        }
+    }
+
+    /// Start collecting a code block's source; its lines begin at the next push.
+    fn begin_code_block(&mut self, lang: String) {
+        let start = self.lines.len();
+        self.code_block = Some(CodeBlock {
+            lang,
+            source: String::new(),
+            lines: start..start,
+        });
+    }
+
+    fn end_code_block(&mut self) {
+        if let Some(mut block) = self.code_block.take() {
+            block.lines.end = self.lines.len();
+            self.code_blocks.push(block);
         }
     }
 
@@ -63,46 +735,122 @@ impl RenderState {
         self.lines.push(line.to_string());
     }
 
+    /// Separate blocks with `block_spacing` blank lines, without stacking
+    /// blanks when several blocks open or close back to back.
     fn push_blank(&mut self) {
         // Flush any pending content first
         self.flush_wrapped();
-        if self.lines.last().map_or(true, |l| !l.is_empty()) {
+        let trailing = self.lines.iter().rev().take_while(|l| l.is_empty()).count();
+        // Nothing to separate from yet: the document never starts with a blank line
+        if trailing == self.lines.len() {
+            return;
+        }
+        for _ in trailing..self.block_spacing {
             self.lines.push(String::new());
         }
     }
 
     /// Flush `current_line` with word wrapping (or truncation in no_wrap mode).
     fn flush_wrapped(&mut self) {
-        if self.current_line.is_empty() {
+        self.wrap_current_line();
+        self.clamp_paragraph();
+    }
+
+    /// Drop the current paragraph's lines past `max_paragraph_lines`, ending
+    /// the last line kept with an ellipsis the first time any are dropped.
+    fn clamp_paragraph(&mut self) {
+        let max = self.max_paragraph_lines;
+        if !self.in_paragraph || max == 0 || self.lines.len() - self.paragraph_start <= max {
+            return;
+        }
+        self.lines.truncate(self.paragraph_start + max);
+        if std::mem::replace(&mut self.paragraph_clamped, true) {
+            return;
+        }
+        let (width, glyphs, use_color) = (self.text_width, self.glyphs, self.use_color);
+        let Some(last) = self.lines.last_mut() else {
+            return;
+        };
+        *last = if style::visible_len(last) + style::visible_len(glyphs.ellipsis) <= width {
+            let reset = if last.contains('\x1b') { style::RESET } else { "" };
+            format!("{}{}{}", last.trim_end(), reset, glyphs.ellipsis)
+        } else {
+            truncate_styled(last, width, &glyphs, use_color)
+        };
+    }
+
+    fn wrap_current_line(&mut self) {
+        if self.current_line.is_empty() && self.line_lead.is_none() && self.task_box.is_none() {
             return;
         }
         let text = std::mem::take(&mut self.current_line);
-        let prefix = self.indent_prefix();
-        let prefix_visible_len = style::visible_len(&prefix);
-        let available = self.width.saturating_sub(prefix_visible_len);
+        let base = self.indent_prefix();
+        // Continuation lines sit under the item text, not under its marker
+        let hang = format!("{}{}", base, " ".repeat(self.hang));
+        let first_prefix = if std::mem::take(&mut self.marker_pending) {
+            match self.task_box.take() {
+                Some(checkbox) => outdent_task_box(&base, &checkbox, self.hang),
+                None => base,
+            }
+        } else {
+            hang.clone()
+        };
+        // Only a paragraph's very first line goes without the hanging indent
+        let extra = if self.in_paragraph { self.hanging_indent } else { 0 };
+        let prefix = format!("{}{}", hang, " ".repeat(extra));
+        let first_prefix = if std::mem::take(&mut self.paragraph_first) {
+            first_prefix
+        } else {
+            format!("{}{}", first_prefix, " ".repeat(extra))
+        };
+        let first_prefix = match self.marker_lead.take() {
+            Some(lead) => {
+                let covered = style::visible_len(&lead);
+                format!("{}{}", lead, first_prefix.get(covered..).unwrap_or_default())
+            }
+            None => first_prefix,
+        };
+        let first_prefix = match self.line_lead.take() {
+            Some(lead) => first_prefix + &lead,
+            None => first_prefix,
+        };
+        let available = self.text_width.saturating_sub(style::visible_len(&prefix));
 
         if available == 0 {
-            self.lines.push(format!("{}{}", prefix, text));
+            self.lines.push(format!("{}{}", first_prefix, text));
             return;
         }
 
         if self.no_wrap {
             // Truncate mode: single line, add ellipsis if it exceeds width
-            let full = format!("{}{}", prefix, text);
-            let visible = style::visible_len(&full);
-            if visible <= self.width {
-                self.lines.push(full);
+            let available = self.text_width.saturating_sub(style::visible_len(&first_prefix));
+            if style::visible_len(&text) <= available {
+                self.lines.push(format!("{}{}", first_prefix, text));
             } else {
-                let truncated = truncate_styled(&full, self.width.saturating_sub(1), self.use_color);
-                self.lines.push(truncated);
+                let truncated =
+                    truncate_styled_at(&text, available, self.truncate, &self.glyphs, self.use_color);
+                self.lines.push(format!("{}{}", first_prefix, truncated));
             }
             return;
         }
 
-        // Word-wrap mode
+        // Right-to-left paragraphs are right-aligned: spaces go between the
+        // prefix and the text, filling the line's unused width
+        let rtl = self.in_paragraph && is_rtl(&text);
+        let align = |line: String, prefix_len: usize, slack: usize| {
+            if !rtl {
+                return line;
+            }
+            let (prefix, content) = line.split_at(prefix_len);
+            format!("{}{}{}", prefix, " ".repeat(slack), content)
+        };
+
+        // Word-wrap mode; the marker on the first line takes the place of the hang
         let segments = split_styled_words(&text);
 
-        let mut line_buf = prefix.clone();
+        let mut limit = self.text_width.saturating_sub(style::visible_len(&first_prefix));
+        let mut prefix_len = first_prefix.len();
+        let mut line_buf = first_prefix;
         let mut line_visible = 0usize;
 
         for seg in &segments {
@@ -111,19 +859,21 @@ impl RenderState {
             if line_visible == 0 {
                 line_buf.push_str(seg);
                 line_visible = seg_visible;
-            } else if line_visible + 1 + seg_visible <= available {
+            } else if line_visible + 1 + seg_visible <= limit {
                 line_buf.push(' ');
                 line_buf.push_str(seg);
                 line_visible += 1 + seg_visible;
             } else {
-                self.lines.push(line_buf);
+                self.lines.push(align(line_buf, prefix_len, limit.saturating_sub(line_visible)));
                 line_buf = format!("{}{}", prefix, seg);
+                prefix_len = prefix.len();
                 line_visible = seg_visible;
+                limit = available;
             }
         }
 
         if line_visible > 0 || !line_buf.is_empty() {
-            self.lines.push(line_buf);
+            self.lines.push(align(line_buf, prefix_len, limit.saturating_sub(line_visible)));
         }
     }
 
@@ -137,76 +887,215 @@ impl RenderState {
                 1 => {
                     codes.push(style::BOLD);
                     codes.push(style::UNDERLINE);
-                    codes.push(style::FG_BRIGHT_WHITE);
+                    codes.push(&self.theme.headings[0]);
                 }
                 2 => {
                     codes.push(style::BOLD);
-                    codes.push(style::FG_BRIGHT_CYAN);
+                    codes.push(&self.theme.headings[1]);
                 }
                 3 => {
                     codes.push(style::BOLD);
-                    codes.push(style::FG_BRIGHT_YELLOW);
+                    codes.push(&self.theme.headings[2]);
                 }
                 _ => {
                     codes.push(style::BOLD);
                 }
             }
         }
-        if self.bold {
+        if self.bold > 0 {
             codes.push(style::BOLD);
         }
-        if self.italic {
+        if self.italic > 0 || self.lead == Lead::Active {
             codes.push(style::ITALIC);
         }
-        if self.strikethrough {
+        if self.strikethrough > 0 {
             codes.push(style::STRIKETHROUGH);
         }
+        if self.link_url.is_some() {
+            codes.push(style::UNDERLINE);
+        }
         style::combine(&codes)
     }
 
     fn current_style_suffix(&self) -> String {
         if !self.use_color {
             String::new()
-        } else if self.bold || self.italic || self.strikethrough || self.in_heading.is_some() {
+        } else if self.bold > 0
+            || self.italic > 0
+            || self.strikethrough > 0
+            || self.in_heading.is_some()
+            || self.lead == Lead::Active
+            || self.link_url.is_some()
+        {
             style::RESET.to_string()
         } else {
             String::new()
         }
     }
 
+    /// In plain `--keep-markers` mode, write an emphasis marker where the
+    /// style would otherwise have started or ended.
+    fn push_marker(&mut self, marker: &str) {
+        if self.use_color || !self.keep_markers {
+            return;
+        }
+        if self.in_table_cell {
+            self.table_cell_buf.push_str(marker);
+        } else {
+            self.current_line.push_str(marker);
+        }
+    }
+
+    /// Deepest indent a list may use: half the width, so nested items always
+    /// keep room for their text.
+    fn max_list_indent(&self) -> usize {
+        (self.width / 2) & !1
+    }
+
+    /// Indent for a list at nesting `depth` (0-based), capped at `max_list_indent`.
+    fn list_indent(&self, depth: usize) -> usize {
+        ((depth + 1) * 2).min(self.max_list_indent())
+    }
+
     fn indent_prefix(&self) -> String {
         let mut prefix = String::new();
-        if self.in_blockquote {
-            if self.use_color {
-                prefix.push_str(&format!("{}  │ {}", style::DIM, style::RESET));
-            } else {
-                prefix.push_str("  | ");
+        // A quote inside a list item draws its bar under the item's text
+        let mut column = 0;
+        for (level, &(at, _)) in self.quotes.iter().enumerate() {
+            if level == 0 && at == 0 {
+                prefix.push_str("  ");
+            }
+            prefix.push_str(&" ".repeat(at.saturating_sub(column)));
+            column = column.max(at);
+            let bars = &self.theme.quote_bars;
+            match bars.get(level % bars.len().max(1)) {
+                Some(color) if self.use_color => {
+                    prefix.push_str(&format!("{}{}{} ", color, self.glyphs.bar, style::RESET));
+                }
+                _ if self.use_color => {
+                    prefix.push_str(self.glyphs.bar);
+                    prefix.push(' ');
+                }
+                _ => prefix.push_str("| "),
             }
         }
-        if self.indent > 0 {
-            prefix.push_str(&" ".repeat(self.indent));
-        }
+        prefix.push_str(&" ".repeat(self.indent.saturating_sub(column)));
         prefix
     }
 }
 
+/// ASCII sequences replaced by `--symbols`, longest first so `<=>` wins over `<=`.
+const SYMBOLS: &[(&str, &str)] = &[
+    ("<=>", "⇔"),
+    ("<->", "↔"),
+    ("->", "→"),
+    ("=>", "⇒"),
+    ("<-", "←"),
+    ("<=", "≤"),
+    (">=", "≥"),
+    ("!=", "≠"),
+];
+
+/// Show control characters other than tab in caret notation (`^[` for escape).
+fn escape_controls(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    for ch in line.chars() {
+        match ch {
+            '\t' => out.push(ch),
+            '\0'..='\x1f' | '\x7f' => {
+                out.push('^');
+                out.push((ch as u8 ^ 0x40) as char);
+            }
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Keep (or drop) the color sequences (`ESC [ … m`) in terminal output, and
+/// show any other escape, like a cursor move or clipboard write, as text.
+fn filter_sgr(line: &str, keep: bool) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(pos) = rest.find('\x1b') {
+        out.push_str(&escape_controls(&rest[..pos]));
+        let seq = &rest[pos..];
+        let params = seq.strip_prefix("\x1b[").map_or(0, |p| {
+            p.len() - p.trim_start_matches(|c: char| c.is_ascii_digit() || c == ';').len()
+        });
+        if seq.starts_with("\x1b[") && seq[2 + params..].starts_with('m') {
+            if keep {
+                out.push_str(&seq[..3 + params]);
+            }
+            rest = &seq[3 + params..];
+        } else {
+            out.push_str("^[");
+            rest = &seq[1..];
+        }
+    }
+    out.push_str(&escape_controls(rest));
+    out
+}
+
+/// Replace each tab with spaces up to the next multiple of `tab_width`,
+/// counting columns from `start_col`.
+fn expand_tabs(text: &str, start_col: usize, tab_width: usize) -> String {
+    let tab_width = tab_width.max(1);
+    let mut out = String::with_capacity(text.len());
+    let mut col = start_col;
+    for ch in text.chars() {
+        if ch == '\t' {
+            let spaces = tab_width - col % tab_width;
+            out.extend(std::iter::repeat_n(' ', spaces));
+            col += spaces;
+        } else {
+            out.push(ch);
+            col += 1;
+        }
+    }
+    out
+}
+
+/// Replace arrow and operator sequences with their Unicode symbols.
+fn substitute_symbols(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    'outer: while let Some(ch) = rest.chars().next() {
+        for (ascii, symbol) in SYMBOLS {
+            if let Some(after) = rest.strip_prefix(ascii) {
+                result.push_str(symbol);
+                rest = after;
+                continue 'outer;
+            }
+        }
+        result.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+    result
+}
+
 /// Split a string (potentially containing ANSI codes) into whitespace-delimited segments,
-/// preserving ANSI codes attached to the words they surround.
+/// preserving ANSI codes attached to the words they surround. A RESET that directly
+/// follows whitespace closes the previous word, so a styled span (e.g. an inline code
+/// background) never extends into the space or line break re-inserted after it.
 fn split_styled_words(text: &str) -> Vec<String> {
-    let mut words = Vec::new();
+    let mut words: Vec<String> = Vec::new();
     let mut current = String::new();
-    let mut in_escape = false;
+    let mut escape = String::new();
     let mut has_visible = false;
 
     for ch in text.chars() {
-        if in_escape {
-            current.push(ch);
+        if !escape.is_empty() {
+            escape.push(ch);
             if ch.is_ascii_alphabetic() {
-                in_escape = false;
+                let seq = std::mem::take(&mut escape);
+                match words.last_mut() {
+                    Some(last) if seq == style::RESET && current.is_empty() => last.push_str(&seq),
+                    _ => current.push_str(&seq),
+                }
             }
         } else if ch == '\x1b' {
-            in_escape = true;
-            current.push(ch);
+            escape.push(ch);
         } else if ch == ' ' || ch == '\t' {
             if has_visible {
                 words.push(std::mem::take(&mut current));
@@ -219,6 +1108,7 @@ fn split_styled_words(text: &str) -> Vec<String> {
         }
     }
 
+    current.push_str(&escape);
     if !current.is_empty() {
         words.push(current);
     }
@@ -226,9 +1116,38 @@ fn split_styled_words(text: &str) -> Vec<String> {
     words
 }
 
+/// Whether most of the letters in `text` are from right-to-left scripts
+/// (Hebrew, Arabic, Syriac, Thaana and their presentation forms).
+fn is_rtl(text: &str) -> bool {
+    let (mut rtl, mut ltr) = (0usize, 0usize);
+    for ch in style::strip_ansi(text).chars().filter(|c| c.is_alphabetic()) {
+        match ch {
+            '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}' => rtl += 1,
+            _ => ltr += 1,
+        }
+    }
+    rtl > ltr
+}
+
+/// Marks a hard break inside a table cell; the cell continues on a new line.
+const CELL_BREAK: char = '\n';
+
 /// Word-wrap a cell's text to fit within `max_width` visible characters.
 /// Returns a Vec of lines. In no_wrap mode, truncates with ellipsis instead.
-fn wrap_cell_text(text: &str, max_width: usize, no_wrap: bool, use_color: bool) -> Vec<String> {
+fn wrap_cell_text(
+    text: &str,
+    max_width: usize,
+    no_wrap: bool,
+    truncate: TruncatePosition,
+    glyphs: &Glyphs,
+    use_color: bool,
+) -> Vec<String> {
+    if text.contains(CELL_BREAK) {
+        return text
+            .split(CELL_BREAK)
+            .flat_map(|part| wrap_cell_text(part, max_width, no_wrap, truncate, glyphs, use_color))
+            .collect();
+    }
     if max_width == 0 {
         return vec![text.to_string()];
     }
@@ -239,7 +1158,7 @@ fn wrap_cell_text(text: &str, max_width: usize, no_wrap: bool, use_color: bool)
     }
 
     if no_wrap {
-        return vec![truncate_styled(text, max_width.saturating_sub(1), use_color)];
+        return vec![truncate_styled_at(text, max_width, truncate, glyphs, use_color)];
     }
 
     let words = split_styled_words(text);
@@ -275,9 +1194,16 @@ fn wrap_cell_text(text: &str, max_width: usize, no_wrap: bool, use_color: bool)
     lines
 }
 
-/// Truncate a string containing ANSI codes to `max_visible` visible characters,
-/// appending an ellipsis character and a RESET if needed.
-fn truncate_styled(text: &str, max_visible: usize, use_color: bool) -> String {
+/// Truncate a string containing ANSI codes to fit `width` columns, ending
+/// with a RESET if needed and the glyph set's ellipsis.
+fn truncate_styled(text: &str, width: usize, glyphs: &Glyphs, use_color: bool) -> String {
+    let room = width.saturating_sub(style::visible_len(glyphs.ellipsis));
+    styled_head(text, room, use_color) + glyphs.ellipsis
+}
+
+/// The first `max_visible` visible characters of `text`, followed by a RESET
+/// if `use_color` is set.
+fn styled_head(text: &str, max_visible: usize, use_color: bool) -> String {
     let mut result = String::new();
     let mut visible = 0;
     let mut in_escape = false;
@@ -292,79 +1218,823 @@ fn truncate_styled(text: &str, max_visible: usize, use_color: bool) -> String {
             in_escape = true;
             result.push(ch);
         } else {
-            if visible >= max_visible {
+            let width = style::char_width(ch);
+            // Combining marks stay with the last character kept
+            if visible >= max_visible && width > 0 {
                 break;
             }
             result.push(ch);
-            visible += 1;
+            visible += width;
         }
     }
 
     if use_color {
         result.push_str(style::RESET);
     }
-    result.push('…');
     result
 }
 
+/// Truncate to fit `width` columns, including an ellipsis placed at `position`.
+pub(crate) fn truncate_styled_at(
+    text: &str,
+    width: usize,
+    position: TruncatePosition,
+    glyphs: &Glyphs,
+    use_color: bool,
+) -> String {
+    let ellipsis = glyphs.ellipsis;
+    let room = width.saturating_sub(style::visible_len(ellipsis));
+    match position {
+        TruncatePosition::End => truncate_styled(text, width, glyphs, use_color),
+        TruncatePosition::Start => {
+            let (escapes, tail) = styled_tail(text, room);
+            format!("{}{}{}", ellipsis, escapes, tail)
+        }
+        TruncatePosition::Middle => {
+            let head_len = room.div_ceil(2);
+            let head = styled_head(text, head_len, use_color);
+            let (escapes, tail) = styled_tail(text, room - head_len);
+            format!("{}{}{}{}", head, ellipsis, escapes, tail)
+        }
+    }
+}
+
+/// Split off the last `max_visible` visible characters of `text`. Returns the escape
+/// codes from the dropped part, so the tail keeps its styling, and the tail itself.
+fn styled_tail(text: &str, max_visible: usize) -> (String, String) {
+    let skip = style::visible_len(text).saturating_sub(max_visible);
+    let mut escapes = String::new();
+    let mut tail = String::new();
+    let mut visible = 0;
+    let mut in_escape = false;
+    // Whether the last visible character was kept; combining marks follow it
+    let mut keep = skip == 0;
+
+    for ch in text.chars() {
+        let target = if visible < skip { &mut escapes } else { &mut tail };
+        if in_escape {
+            target.push(ch);
+            if ch.is_ascii_alphabetic() {
+                in_escape = false;
+            }
+        } else if ch == '\x1b' {
+            in_escape = true;
+            target.push(ch);
+        } else {
+            let width = style::char_width(ch);
+            if width > 0 {
+                keep = visible >= skip;
+            }
+            if keep {
+                tail.push(ch);
+            }
+            visible += width;
+        }
+    }
+
+    (escapes, tail)
+}
+
 /// Render a stream of markdown events into styled terminal lines.
-pub fn render(events: Vec<Event<'_>>, width: u16, use_color: bool, no_wrap: bool) -> Vec<String> {
-    let mut state = RenderState::new(width, use_color, no_wrap);
+pub fn render(events: Vec<Event<'_>>, width: u16, opts: &RenderOptions) -> Vec<String> {
+    render_document(events, width, opts).lines
+}
 
+/// Like [`render`], but also returns the position of every link URL.
+pub fn render_document(events: Vec<Event<'_>>, width: u16, opts: &RenderOptions) -> Rendered {
+    let mut renderer = Renderer::new(width, opts);
+    let events = filter_events(events, opts);
+    if opts.glossary {
+        renderer.state.glossary = glossary_columns(&events, renderer.state.width);
+    }
     for event in events {
-        match event {
-            Event::Start(tag) => handle_start_tag(&mut state, &tag),
-            Event::End(tag) => handle_end_tag(&mut state, &tag),
-            Event::Text(text) => handle_text(&mut state, &text),
-            Event::Code(code) => handle_code(&mut state, &code),
-            Event::SoftBreak => handle_soft_break(&mut state),
-            Event::HardBreak => handle_hard_break(&mut state),
-            Event::Rule => handle_rule(&mut state),
-            Event::TaskListMarker(checked) => handle_task_marker(&mut state, checked),
-            _ => {}
-        }
+        renderer.event(event);
     }
+    renderer.finish()
+}
 
-    state.flush_wrapped();
-    state.lines
+/// Like [`render_document`], but renders events as they arrive instead of
+/// collecting them first, so a huge document's events are never all in
+/// memory at once. `grep` and `glossary` look at the whole document, so with
+/// either set the events are collected after all.
+pub fn render_stream<'a>(
+    events: impl IntoIterator<Item = Event<'a>>,
+    width: u16,
+    opts: &RenderOptions,
+) -> Rendered {
+    if opts.grep.is_some() || opts.glossary {
+        return render_document(events.into_iter().collect(), width, opts);
+    }
+    let mut renderer = Renderer::new(width, opts);
+    for event in omit_elements(events.into_iter(), opts) {
+        renderer.event(event);
+    }
+    renderer.finish()
 }
 
-fn handle_start_tag(state: &mut RenderState, tag: &Tag) {
-    match tag {
-        Tag::Heading { level, .. } => {
-            state.push_blank();
-            state.in_heading = Some(*level as u8);
+/// Like [`render_document`], but shows every event to `visitor` first, which
+/// can inspect it, draw it itself, or let it render as usual.
+pub fn render_with(
+    events: Vec<Event<'_>>,
+    width: u16,
+    opts: &RenderOptions,
+    visitor: &mut impl Visitor,
+) -> Rendered {
+    let mut renderer = Renderer::new(width, opts);
+    let events = filter_events(events, opts);
+    if opts.glossary {
+        renderer.state.glossary = glossary_columns(&events, renderer.state.width);
+    }
+    for event in events {
+        if visitor.visit(&event, &mut renderer) {
+            renderer.event(event);
         }
-        Tag::Paragraph => {
-            if !state.in_code_block {
-                state.push_blank();
+    }
+    renderer.finish()
+}
+
+/// Drop the element types the `no_*` options leave out, then apply the
+/// `grep` option's filter, if any.
+fn filter_events<'a>(events: Vec<Event<'a>>, opts: &RenderOptions) -> Vec<Event<'a>> {
+    let events = omit_elements(events.into_iter(), opts).collect();
+    match &opts.grep {
+        Some(pattern) => grep::filter(events, pattern),
+        None => events,
+    }
+}
+
+/// For each list in `events`, in order, the term column width for a glossary
+/// layout: the widest term, capped at a third of `width`. `None` for lists
+/// with any item not of the form `**Term** — definition`.
+fn glossary_columns(events: &[Event<'_>], width: usize) -> VecDeque<Option<usize>> {
+    events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| matches!(event, Event::Start(Tag::List(_))))
+        .map(|(i, _)| glossary_width(&events[i + 1..]).map(|w| w.min(width / 3)))
+        .collect()
+}
+
+/// Widest term among the items of the list starting at `events`, if every
+/// item is a glossary entry.
+fn glossary_width(events: &[Event<'_>]) -> Option<usize> {
+    let mut widest = None;
+    let mut depth = 0usize;
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::Item) if depth == 0 => {
+                widest = widest.max(Some(glossary_term(&events[i + 1..])?));
+                depth += 1;
             }
+            Event::Start(_) => depth += 1,
+            Event::End(TagEnd::List(_)) if depth == 0 => break,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ => {}
         }
-        Tag::BlockQuote(_) => {
-            state.in_blockquote = true;
-            state.push_blank();
+    }
+    widest
+}
+
+/// Width of an item's bold term, if the item starts with one followed by a
+/// dash or colon.
+fn glossary_term(events: &[Event<'_>]) -> Option<usize> {
+    let events = match events {
+        [Event::Start(Tag::Paragraph), rest @ ..] => rest,
+        _ => events,
+    };
+    let [Event::Start(Tag::Strong), rest @ ..] = events else {
+        return None;
+    };
+    let end = rest.iter().position(|e| matches!(e, Event::End(TagEnd::Strong)))?;
+    let mut width = 0;
+    for event in &rest[..end] {
+        match event {
+            Event::Text(text) => width += style::visible_len(text),
+            // Padded with spaces, or backticks without color
+            Event::Code(code) => width += style::visible_len(code) + 2,
+            _ => return None,
         }
-        Tag::CodeBlock(kind) => {
-            state.in_code_block = true;
-            state.push_blank();
-            if let CodeBlockKind::Fenced(lang) = kind {
-                if !lang.is_empty() {
-                    let label = style::styled(
-                        &format!("  ╭─ {} ", lang),
-                        &[style::DIM],
-                        state.use_color,
-                    );
-                    state.push_line(&label);
-                } else {
-                    let label = style::styled("  ╭───", &[style::DIM], state.use_color);
-                    state.push_line(&label);
-                }
+    }
+    match rest.get(end + 1) {
+        Some(Event::Text(text)) if strip_glossary_dash(text).is_some() => Some(width),
+        _ => None,
+    }
+}
+
+/// The definition after a glossary term's separator: `—`, `–`, `-` or `:`.
+fn strip_glossary_dash(text: &str) -> Option<&str> {
+    let text = text.trim_start();
+    ["—", "–", "-", ":"]
+        .iter()
+        .find_map(|dash| text.strip_prefix(dash))
+        .map(str::trim_start)
+}
+
+/// Remove omitted tables, code blocks and images along with everything
+/// inside them.
+fn omit_elements<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+    opts: &RenderOptions,
+) -> impl Iterator<Item = Event<'a>> {
+    let (no_tables, no_code, no_images) = (opts.no_tables, opts.no_code, opts.no_images);
+    let omitted = move |event: &Event| match event {
+        Event::Start(Tag::Table(_)) | Event::End(TagEnd::Table) => no_tables,
+        Event::Start(Tag::CodeBlock(_)) | Event::End(TagEnd::CodeBlock) => no_code,
+        Event::Start(Tag::Image { .. }) | Event::End(TagEnd::Image) => no_images,
+        _ => false,
+    };
+    let mut depth = 0usize;
+    events.filter(move |event| {
+        if omitted(event) {
+            if matches!(event, Event::Start(_)) {
+                depth += 1;
             } else {
-                let label = style::styled("  ╭───", &[style::DIM], state.use_color);
-                state.push_line(&label);
+                depth -= 1;
             }
+            return false;
         }
-        Tag::List(first) => {
+        depth == 0
+    })
+}
+
+/// Hook for intercepting events in [`render_with`].
+pub trait Visitor {
+    /// Called with each event before it is rendered. Return `false` to skip
+    /// the default rendering, e.g. after drawing it with [`Renderer::push_line`].
+    /// Skip a tag's start and end together, along with everything between.
+    fn visit(&mut self, event: &Event<'_>, renderer: &mut Renderer) -> bool;
+}
+
+/// Incremental renderer: feed it parser events one at a time, then call
+/// [`finish`](Renderer::finish) for the document.
+pub struct Renderer {
+    state: RenderState,
+    stats: Option<StatsCounter>,
+}
+
+impl Renderer {
+    pub fn new(width: u16, opts: &RenderOptions) -> Self {
+        Self {
+            state: RenderState::new(width, opts),
+            stats: opts.stats.then(StatsCounter::default),
+        }
+    }
+
+    /// Render one event.
+    pub fn event(&mut self, event: Event<'_>) {
+        if let Some(stats) = self.stats.as_mut() {
+            stats.add(&event);
+        }
+        let state = &mut self.state;
+        match event {
+            Event::Start(tag) => handle_start_tag(state, &tag),
+            Event::End(tag) => handle_end_tag(state, &tag),
+            Event::Text(text) => handle_text(state, &text),
+            Event::Code(code) => handle_code(state, &code),
+            Event::SoftBreak => handle_soft_break(state),
+            Event::HardBreak => handle_hard_break(state),
+            Event::Rule => handle_rule(state),
+            Event::TaskListMarker(checked) => handle_task_marker(state, checked),
+            Event::FootnoteReference(label) => handle_footnote_reference(state, &label),
+            Event::Html(html) => {
+                if let Some(block) = state.html_block.as_mut() {
+                    block.push_str(&html);
+                }
+            }
+            Event::InlineHtml(html) => handle_inline_html(state, &html),
+            Event::InlineMath(source) => handle_text(state, &format!("${}$", source)),
+            Event::DisplayMath(source) => handle_display_math(state, &source),
+        }
+    }
+
+    /// Append a finished line as is, after any text still being wrapped.
+    pub fn push_line(&mut self, line: &str) {
+        self.state.flush_wrapped();
+        self.state.push_line(line);
+    }
+
+    /// Lines rendered so far, not counting a paragraph still being wrapped.
+    pub fn lines(&self) -> &[String] {
+        &self.state.lines
+    }
+
+    /// Finish the document: flush pending text and add the link list and
+    /// stats footer if enabled.
+    pub fn finish(self) -> Rendered {
+        let mut state = self.state;
+        state.flush_wrapped();
+        let ref_links = state.link_refs.then(|| push_link_refs(&mut state));
+        if let Some(counter) = self.stats {
+            push_stats(&mut state, &counter.stats);
+        }
+        // No style may carry over into the next line or the pager's padding
+        for line in &mut state.lines {
+            if line.contains('\x1b') && !line.ends_with(style::RESET) {
+                line.push_str(style::RESET);
+            }
+        }
+        if state.ascii {
+            for line in &mut state.lines {
+                *line = to_ascii(line);
+                if !state.use_color {
+                    // Escapes can only come from the input here
+                    line.retain(|c| !c.is_control() || c == '\t');
+                }
+            }
+        }
+        let links = ref_links.unwrap_or_else(|| locate_links(&state.lines, &state.link_urls));
+        let footnotes = locate_footnotes(&state.lines, &state.footnote_refs, &state.footnote_defs);
+        Rendered {
+            lines: state.lines,
+            links,
+            code_blocks: state.code_blocks,
+            footnotes,
+            squeezed_tables: state.squeezed_tables,
+        }
+    }
+}
+
+/// Counts shown by the `--stats` footer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DocumentStats {
+    /// Words of prose, excluding code and URLs.
+    pub words: usize,
+    pub headings: usize,
+}
+
+/// Average silent reading speed used for the reading time estimate.
+const WORDS_PER_MINUTE: usize = 200;
+
+impl DocumentStats {
+    /// Estimated minutes to read the prose, rounded up.
+    pub fn reading_minutes(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE)
+    }
+}
+
+/// Count prose words and headings. Text inside code blocks, inline code and
+/// tokens that look like URLs are not words.
+pub fn document_stats(events: &[Event<'_>]) -> DocumentStats {
+    let mut counter = StatsCounter::default();
+    for event in events {
+        counter.add(event);
+    }
+    counter.stats
+}
+
+/// Running [`DocumentStats`] over an event stream.
+#[derive(Default)]
+struct StatsCounter {
+    stats: DocumentStats,
+    in_code_block: bool,
+}
+
+impl StatsCounter {
+    fn add(&mut self, event: &Event<'_>) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => self.stats.headings += 1,
+            Event::Start(Tag::CodeBlock(_)) => self.in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => self.in_code_block = false,
+            Event::Text(text) if !self.in_code_block => {
+                self.stats.words += text
+                    .split_whitespace()
+                    .filter(|w| !w.contains("://") && !w.starts_with("www."))
+                    .filter(|w| w.chars().any(char::is_alphanumeric))
+                    .count();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Append the stats footer below a dim rule.
+fn push_stats(state: &mut RenderState, stats: &DocumentStats) {
+    state.push_blank();
+    let rule = state.glyphs.rule.repeat(state.width);
+    state.push_line(&style::styled(&rule, &[style::DIM], state.use_color));
+    let plural = |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+    let summary = format!(
+        "{}, {} read, {}",
+        plural(stats.words, "word"),
+        plural(stats.reading_minutes(), "min"),
+        plural(stats.headings, "heading")
+    );
+    state.push_line(&style::styled(&summary, &[style::DIM], state.use_color));
+}
+
+/// ASCII stand-ins for common typography in the input; the renderer's own
+/// glyphs come from [`Glyphs`].
+const ASCII_TYPOGRAPHY: &[(char, &str)] = &[
+    ('—', "--"), ('–', "-"), ('‘', "'"), ('’', "'"), ('“', "\""), ('”', "\""),
+    ('→', "->"), ('←', "<-"), ('↔', "<->"), ('⇒', "=>"), ('⇔', "<=>"), ('≤', "<="),
+    ('≥', ">="), ('≠', "!="), ('\u{a0}', " "),
+];
+
+/// Transliterate `line` to 7-bit ASCII. Characters without a stand-in become `?`.
+pub fn to_ascii(line: &str) -> String {
+    let (unicode, ascii) = (Glyphs::UNICODE.all(), Glyphs::ASCII.all());
+    let mut out = String::with_capacity(line.len());
+    for ch in line.chars() {
+        if ch.is_ascii() {
+            out.push(ch);
+        } else if let Some(i) = unicode.iter().position(|glyph| glyph.chars().eq([ch])) {
+            out.push_str(ascii[i]);
+        } else if let Some((_, stand_in)) = ASCII_TYPOGRAPHY.iter().find(|(glyph, _)| *glyph == ch) {
+            out.push_str(stand_in);
+        } else {
+            out.push('?');
+        }
+    }
+    out
+}
+
+/// Blank columns between side-by-side columns in [`columns`].
+pub const COLUMN_GUTTER: usize = 2;
+
+/// Width of each of `n` columns fitting side by side in `width`.
+pub fn column_width(width: u16, n: usize) -> u16 {
+    let n = n.max(1);
+    let gutters = (COLUMN_GUTTER * (n - 1)) as u16;
+    (width.saturating_sub(gutters) / n as u16).max(1)
+}
+
+/// Lay out a document rendered at `col_width` in `n` side-by-side columns,
+/// newspaper style: the first column holds the first third (for three
+/// columns) of the lines, and so on. Lines wider than a column are cut with
+/// an ellipsis. Link positions move with their lines.
+pub fn columns(doc: Rendered, n: usize, col_width: usize, glyphs: &Glyphs) -> Rendered {
+    let n = n.max(1);
+    let rows = doc.lines.len().div_ceil(n);
+    if n == 1 || rows == 0 {
+        return doc;
+    }
+    let gutter = " ".repeat(COLUMN_GUTTER);
+    let mut lines = vec![String::new(); rows];
+    for (i, line) in doc.lines.iter().enumerate() {
+        let row = &mut lines[i % rows];
+        if i >= rows {
+            row.push_str(&gutter);
+        }
+        // Unwrapped lines such as code are cut so they can't push columns apart
+        let len = style::visible_len(line);
+        if len > col_width {
+            let use_color = line.contains('\x1b');
+            row.push_str(&truncate_styled(line, col_width, glyphs, use_color));
+        } else {
+            row.push_str(line);
+        }
+        // Pad every column but the last so the next one starts aligned
+        if i / rows < n - 1 {
+            row.push_str(&" ".repeat(col_width.saturating_sub(len)));
+        }
+    }
+    let footnotes = doc
+        .footnotes
+        .into_iter()
+        .map(|note| FootnoteSpan {
+            refs: note.refs.iter().map(|line| line % rows).collect(),
+            definition: note.definition.map(|line| line % rows),
+            ..note
+        })
+        .collect();
+    let links = doc
+        .links
+        .into_iter()
+        .map(|link| {
+            let shift = (link.line / rows) * (col_width + COLUMN_GUTTER);
+            LinkSpan {
+                line: link.line % rows,
+                start: link.start + shift,
+                end: link.end + shift,
+                ..link
+            }
+        })
+        .collect();
+    // A block split across two columns has no single line range left, so it's dropped
+    let code_blocks = doc
+        .code_blocks
+        .into_iter()
+        .filter(|block| block.lines.start / rows == block.lines.end.saturating_sub(1) / rows)
+        .map(|block| {
+            let start = block.lines.start % rows;
+            CodeBlock {
+                lines: start..start + block.lines.len(),
+                ..block
+            }
+        })
+        .collect();
+    Rendered {
+        lines,
+        links,
+        code_blocks,
+        footnotes,
+        squeezed_tables: doc.squeezed_tables,
+    }
+}
+
+/// Keep only the first `max` lines, appending a dim `… (truncated)` marker
+/// when anything was cut.
+pub fn head(mut lines: Vec<String>, max: usize, glyphs: &Glyphs, use_color: bool) -> Vec<String> {
+    if lines.len() > max {
+        lines.truncate(max);
+        let marker = format!("{} (truncated)", glyphs.ellipsis);
+        lines.push(style::styled(&marker, &[style::DIM], use_color));
+    }
+    lines
+}
+
+/// Remove trailing whitespace from a rendered line. Escape sequences in the
+/// trimmed tail are kept, so a closing reset still ends the line.
+pub fn trim_trailing(line: &str) -> String {
+    let mut keep_end = 0;
+    let mut tail_escapes = String::new();
+    let mut escape_start = None;
+    for (i, ch) in line.char_indices() {
+        if let Some(start) = escape_start {
+            if ch.is_ascii_alphabetic() {
+                tail_escapes.push_str(&line[start..i + 1]);
+                escape_start = None;
+            }
+        } else if ch == '\x1b' {
+            escape_start = Some(i);
+        } else if !ch.is_whitespace() {
+            keep_end = i + ch.len_utf8();
+            tail_escapes.clear();
+        }
+    }
+    if keep_end == line.len() {
+        return line.to_string();
+    }
+    format!("{}{}", &line[..keep_end], tail_escapes)
+}
+
+/// Lines wider than `width` columns, as `(index, visible width)` pairs.
+/// Any hit means wrapping failed, usually on an unbreakable token.
+pub fn overflowing_lines(lines: &[String], width: usize) -> Vec<(usize, usize)> {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| (i, style::visible_len(line)))
+        .filter(|&(_, len)| len > width)
+        .collect()
+}
+
+/// Hard-wrap plain (uncolored) lines at exactly `width` columns, code included.
+/// Continuation lines repeat the original line's indent and quote/code bars.
+pub fn fold(lines: Vec<String>, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut folded = Vec::with_capacity(lines.len());
+    for line in lines {
+        // One entry per column: a character plus any combining marks after it
+        let mut cells: Vec<String> = Vec::new();
+        for ch in line.chars() {
+            match cells.last_mut() {
+                Some(cell) if style::char_width(ch) == 0 => cell.push(ch),
+                _ => cells.push(ch.to_string()),
+            }
+        }
+        if cells.len() <= width {
+            folded.push(line);
+            continue;
+        }
+        let prefix_len = cells
+            .iter()
+            .take_while(|c| [" ", Glyphs::UNICODE.bar, "|"].contains(&c.as_str()))
+            .count();
+        // A prefix eating most of the line would leave no room for content
+        let prefix: String = if prefix_len * 2 <= width {
+            cells[..prefix_len].concat()
+        } else {
+            String::new()
+        };
+        let room = width - prefix.chars().count();
+        folded.push(cells[..width].concat());
+        for chunk in cells[width..].chunks(room) {
+            folded.push(format!("{}{}", prefix, chunk.concat()));
+        }
+    }
+    folded
+}
+
+/// Format the `--link-refs` footer: `[N] url` entries with right-aligned
+/// numbers. URLs longer than the width break anywhere, continuing under the
+/// URL's first column. Returns each entry's lines, one entry per URL.
+fn format_link_refs(urls: &[String], width: usize) -> Vec<Vec<String>> {
+    let num_width = format!("[{}]", urls.len()).len();
+    let indent = num_width + 1;
+    let room = width.saturating_sub(indent).max(1);
+    let mut entries = Vec::new();
+    for (i, url) in urls.iter().enumerate() {
+        let chars: Vec<char> = url.chars().collect();
+        let mut lines = Vec::new();
+        for (j, chunk) in chars.chunks(room).enumerate() {
+            let chunk: String = chunk.iter().collect();
+            if j == 0 {
+                let num = format!("[{}]", i + 1);
+                lines.push(format!("{:>w$} {}", num, chunk, w = num_width));
+            } else {
+                lines.push(format!("{}{}", " ".repeat(indent), chunk));
+            }
+        }
+        entries.push(lines);
+    }
+    entries
+}
+
+/// Append the link reference footer, returning where each entry's URL starts.
+fn push_link_refs(state: &mut RenderState) -> Vec<LinkSpan> {
+    if state.link_urls.is_empty() {
+        return Vec::new();
+    }
+    let urls = std::mem::take(&mut state.link_urls);
+    state.push_blank();
+    let heading = style::styled("References", &[style::BOLD], state.use_color);
+    state.push_line(&heading);
+
+    let indent = format!("[{}] ", urls.len()).len();
+    let mut spans = Vec::new();
+    for (entry, url) in format_link_refs(&urls, state.width).into_iter().zip(&urls) {
+        let mut lines = entry.into_iter();
+        let Some(first) = lines.next() else {
+            continue;
+        };
+        spans.push(LinkSpan {
+            line: state.lines.len(),
+            start: indent,
+            end: first.chars().count(),
+            url: url.clone(),
+        });
+        let (num, rest) = first.split_at(indent);
+        let first = format!("{}{}", style::styled(num, &[style::DIM], state.use_color), rest);
+        state.push_line(&first);
+        for line in lines {
+            state.push_line(&line);
+        }
+    }
+    spans
+}
+
+/// Find each link's ` (url)` display in document order. URLs contain no spaces,
+/// so wrapping never splits one across lines; truncated URLs are skipped.
+fn locate_links(lines: &[String], urls: &[String]) -> Vec<LinkSpan> {
+    let mut spans = Vec::new();
+    let mut line_idx = 0;
+    let mut byte_idx = 0;
+
+    for url in urls {
+        let needle = format!("({})", url);
+        let mut search = (line_idx, byte_idx);
+        while search.0 < lines.len() {
+            let line = &lines[search.0];
+            if let Some(pos) = line[search.1..].find(&needle) {
+                let at = search.1 + pos;
+                let start = style::visible_len(&line[..at]) + 1;
+                spans.push(LinkSpan {
+                    line: search.0,
+                    start,
+                    end: start + style::visible_len(url),
+                    url: url.clone(),
+                });
+                line_idx = search.0;
+                byte_idx = at + needle.len();
+                break;
+            }
+            search = (search.0 + 1, 0);
+        }
+    }
+
+    spans
+}
+
+/// Find each footnote reference's `[label]` marker, searching from the line it
+/// was seen on, and pair references with their definitions by label.
+fn locate_footnotes(
+    lines: &[String],
+    refs: &[(String, usize)],
+    defs: &[(String, usize)],
+) -> Vec<FootnoteSpan> {
+    let mut notes: Vec<FootnoteSpan> = Vec::new();
+    let mut entry = |label: &str| match notes.iter().position(|n| n.label == label) {
+        Some(i) => i,
+        None => {
+            notes.push(FootnoteSpan {
+                label: label.to_string(),
+                refs: Vec::new(),
+                definition: None,
+            });
+            notes.len() - 1
+        }
+    };
+    let mut found = Vec::new();
+    for (label, from) in refs {
+        let marker = format!("[{}]", label);
+        if let Some(line) = (*from..lines.len()).find(|&i| style::strip_ansi(&lines[i]).contains(&marker)) {
+            found.push((entry(label), Some(line), None));
+        }
+    }
+    for (label, line) in defs {
+        found.push((entry(label), None, Some(*line)));
+    }
+    for (i, reference, definition) in found {
+        notes[i].refs.extend(reference);
+        notes[i].definition = definition.or(notes[i].definition);
+    }
+    notes
+}
+
+fn handle_start_tag(state: &mut RenderState, tag: &Tag) {
+    // Only a paragraph directly followed by a table can become its caption
+    let previous_paragraph = state.last_paragraph.take();
+    match tag {
+        Tag::Heading { level, .. } => {
+            state.push_blank();
+            state.in_heading = Some(*level as u8);
+            if state.lead == Lead::BeforeTitle && *level as u8 == 1 {
+                state.lead = Lead::Pending;
+            }
+        }
+        Tag::Paragraph if !state.in_code_block => {
+            // A loose list item's first paragraph goes on its marker's line,
+            // with the blank line before the marker
+            let marker = if state.marker_pending {
+                std::mem::take(&mut state.current_line)
+            } else {
+                String::new()
+            };
+            state.push_blank();
+            state.current_line = marker;
+            state.in_paragraph = true;
+            state.paragraph_first = true;
+            state.paragraph_clamped = false;
+            state.paragraph_start = state.lines.len();
+            let top_level = state.quotes.is_empty()
+                && state.list_stack.is_empty()
+                && state.footnote_label.is_none();
+            if state.lead == Lead::Pending && top_level {
+                state.lead = Lead::Active;
+            }
+            // A footnote definition's label leads its first paragraph
+            if let Some(label) = state.footnote_label.take() {
+                state.current_line = format!("{} ", footnote_marker(state, &label));
+            }
+        }
+        Tag::FootnoteDefinition(label) => {
+            state.push_blank();
+            state.footnote_defs.push((label.to_string(), state.lines.len()));
+            state.footnote_label = Some(label.to_string());
+        }
+        Tag::BlockQuote(_) => {
+            // An item opening with a quote draws its marker on the quote's first line
+            let marker_only = state.marker_pending && style::visible_len(&state.current_line) == state.hang;
+            let marker = marker_only.then(|| {
+                state.marker_pending = false;
+                std::mem::take(&mut state.current_line)
+            });
+            // Flush text of an enclosing quote before the bar count changes
+            state.flush_wrapped();
+            state.quotes.push((state.indent + state.hang, state.hang));
+            state.hang = 0;
+            match marker {
+                Some(marker) => state.marker_lead = Some(format!("{}{}", " ".repeat(state.indent), marker)),
+                None => state.push_blank(),
+            }
+        }
+        Tag::CodeBlock(CodeBlockKind::Fenced(info))
+            if state.raw_fences.contains(&FenceInfo::parse(info).lang) =>
+        {
+            state.in_code_block = true;
+            state.in_raw_block = true;
+            state.push_blank();
+            state.begin_code_block(FenceInfo::parse(info).lang);
+        }
+        Tag::CodeBlock(kind) => {
+            state.in_code_block = true;
+            // An item opening with code keeps its marker right above the box
+            if state.marker_pending && style::visible_len(&state.current_line) == state.hang {
+                state.flush_wrapped();
+            } else {
+                state.push_blank();
+            }
+            // Boxes never touch, even without block spacing
+            if state.code_blocks.last().is_some_and(|b| b.lines.end == state.lines.len()) {
+                state.push_line("");
+            }
+            let lang = match kind {
+                CodeBlockKind::Fenced(info) => FenceInfo::parse(info).lang,
+                CodeBlockKind::Indented => String::new(),
+            };
+            state.begin_code_block(lang);
+            if let CodeBlockKind::Fenced(info) = kind {
+                let lang = FenceInfo::parse(info).lang;
+                state.in_ansi_block = ANSI_FENCES.contains(&lang.as_str());
+                state.code_lang = highlight::find(&lang).filter(|_| !state.in_ansi_block);
+            }
+            // Drawn once the block's width is known
+            state.push_line("");
+        }
+        Tag::List(first) => {
+            // Emit the parent item's text before the nested list changes the indent
+            state.flush_wrapped();
             if state.list_stack.is_empty() {
                 state.push_blank();
             }
@@ -373,48 +2043,89 @@ fn handle_start_tag(state: &mut RenderState, tag: &Tag) {
                 Some(start) => state.list_stack.push(ListContext::Ordered(*start)),
                 None => state.list_stack.push(ListContext::Unordered(depth)),
             }
-            state.indent = (depth + 1) * 2;
+            // A nested list starts under its parent item's text
+            state.indent = if depth == 0 {
+                state.list_indent(0)
+            } else {
+                (state.indent + state.hang).min(state.max_list_indent())
+            };
+            state.list_indents.push((state.indent, state.hang));
+            state.hang = 0;
+            let glossary = state.glossary.pop_front().flatten();
+            state.glossary_lists.push(glossary);
         }
         Tag::Item => {
             state.flush_wrapped();
-            let prefix = state.indent_prefix();
             let marker = match state.list_stack.last() {
                 Some(ListContext::Unordered(depth)) => {
                     match depth {
-                        0 => "• ".to_string(),
-                        1 => "◦ ".to_string(),
-                        _ => "▪ ".to_string(),
+                        // Nested past the indent cap: same indent, distinct glyph
+                        d if (d + 1) * 2 > state.max_list_indent() => {
+                            format!("{} ", state.glyphs.deep_bullet)
+                        }
+                        d => match state.bullets.get(*d).or(state.bullets.last()) {
+                            Some(bullet) => format!("{} ", bullet),
+                            None => format!("{} ", state.glyphs.bullets[0]),
+                        },
                     }
                 }
                 Some(ListContext::Ordered(num)) => {
-                    let s = format!("{}. ", num);
+                    let s = if state.ordered_style == OrderedStyle::Legal {
+                        format!("{} ", legal_number(&state.list_stack))
+                    } else {
+                        format!("{}. ", state.ordered_style.label(*num))
+                    };
                     if let Some(ListContext::Ordered(n)) = state.list_stack.last_mut() {
                         *n += 1;
                     }
                     s
                 }
-                None => "• ".to_string(),
+                None => format!("{} ", state.glyphs.bullets[0]),
             };
+            state.hang = style::visible_len(&marker);
+            state.marker_pending = true;
+            state.glossary_term = state.glossary_lists.last().copied().flatten();
             let styled_marker = if state.use_color {
-                style::styled(&marker, &[style::FG_CYAN], state.use_color)
+                style::styled(&marker, &[&state.theme.accent], state.use_color)
             } else {
                 marker
             };
-            state.current_line = format!("{}{}", prefix, styled_marker);
+            // The blockquote bar and list indent are applied by flush_wrapped
+            state.current_line = styled_marker;
         }
         Tag::Emphasis => {
-            state.italic = true;
+            state.italic += 1;
+            state.push_marker("_");
         }
         Tag::Strong => {
-            state.bold = true;
+            state.bold += 1;
+            state.push_marker("*");
         }
         Tag::Strikethrough => {
-            state.strikethrough = true;
+            state.strikethrough += 1;
+            state.push_marker("~~");
         }
         Tag::Link { dest_url, .. } => {
             state.link_url = Some(dest_url.to_string());
         }
+        Tag::Image { dest_url, title, .. } => {
+            if title.as_ref() == parser::UNRESOLVED_IMAGE {
+                state.missing_image = Some(dest_url.to_string());
+            }
+            let open = style::styled("[image: ", &[style::DIM], state.use_color);
+            state.current_line.push_str(&open);
+        }
+        Tag::HtmlBlock => {
+            state.html_block = Some(String::new());
+        }
         Tag::Table(alignments) => {
+            if state.table_captions && state.quotes.is_empty() && state.list_stack.is_empty() {
+                if let Some((start, end)) = previous_paragraph {
+                    if end == start + 1 && end == state.lines.len() {
+                        state.table_caption = state.lines.pop();
+                    }
+                }
+            }
             state.push_blank();
             state.table_alignments = alignments.clone();
             state.table_rows.clear();
@@ -436,49 +2147,149 @@ fn handle_start_tag(state: &mut RenderState, tag: &Tag) {
 
 fn handle_end_tag(state: &mut RenderState, tag: &TagEnd) {
     match tag {
-        TagEnd::Heading(_level) => {
+        TagEnd::Heading(level) => {
             state.flush_wrapped();
+            if state.heading_style == HeadingStyle::Underline && (*level as u8) <= 2 {
+                let glyph = if *level as u8 == 1 {
+                    state.glyphs.double_rule
+                } else {
+                    state.glyphs.rule
+                };
+                let prefix = state.indent_prefix();
+                let rule = glyph.repeat(state.text_width.saturating_sub(style::visible_len(&prefix)));
+                let line = if state.use_color {
+                    format!("{}{}{}{}", prefix, state.current_style_prefix(), rule, style::RESET)
+                } else {
+                    format!("{}{}", prefix, rule)
+                };
+                state.push_line(&line);
+            }
             state.in_heading = None;
         }
+        TagEnd::Image => {
+            if let Some(label) = state.missing_image.take() {
+                let flag = format!(" (unresolved: {})", label);
+                let flag = style::styled(&flag, &[&state.theme.removed], state.use_color);
+                state.current_line.push_str(&flag);
+            }
+            let close = style::styled("]", &[style::DIM], state.use_color);
+            state.current_line.push_str(&close);
+        }
+        TagEnd::HtmlBlock => {
+            if let Some(html) = state.html_block.take() {
+                handle_comment_block(state, &html);
+            }
+        }
         TagEnd::Paragraph => {
             state.flush_wrapped();
+            state.in_paragraph = false;
+            if state.lead == Lead::Active {
+                state.lead = Lead::Done;
+            }
+            state.last_paragraph = Some((state.paragraph_start, state.lines.len()));
         }
         TagEnd::BlockQuote(_) => {
             state.flush_wrapped();
-            state.in_blockquote = false;
+            if let Some((_, hang)) = state.quotes.pop() {
+                state.hang = hang;
+            }
+        }
+        TagEnd::CodeBlock if state.in_raw_block => {
+            state.in_raw_block = false;
+            state.in_code_block = false;
+            state.end_code_block();
         }
         TagEnd::CodeBlock => {
-            let label = style::styled("  ╰───", &[style::DIM], state.use_color);
-            state.push_line(&label);
+            draw_code_box(state);
+            state.end_code_block();
             state.in_code_block = false;
+            state.code_lang = None;
+            state.in_ansi_block = false;
         }
         TagEnd::List(_) => {
+            if state.list_stack.len() == 1 {
+                let (done, total) = std::mem::take(&mut state.tasks);
+                if state.task_summary && total > 0 {
+                    let summary = format!(
+                        "{}/{} tasks complete ({}%)",
+                        done,
+                        total,
+                        done * 100 / total
+                    );
+                    let line = format!(
+                        "{}{}",
+                        state.indent_prefix(),
+                        style::styled(&summary, &[style::DIM], state.use_color)
+                    );
+                    state.push_line(&line);
+                }
+            }
             state.list_stack.pop();
-            state.indent = state.list_stack.len() * 2;
+            state.glossary_lists.pop();
+            // Later paragraphs of the parent item line up with its text again
+            if let Some((_, hang)) = state.list_indents.pop() {
+                state.hang = hang;
+            }
+            state.indent = state.list_indents.last().map_or(0, |&(indent, _)| indent);
             if state.list_stack.is_empty() {
                 state.push_blank();
             }
         }
         TagEnd::Item => {
             state.flush_wrapped();
+            state.hang = 0;
         }
         TagEnd::Emphasis => {
-            state.italic = false;
+            state.push_marker("_");
+            state.italic = state.italic.saturating_sub(1);
         }
         TagEnd::Strong => {
-            state.bold = false;
+            state.push_marker("*");
+            state.bold = state.bold.saturating_sub(1);
+            if let Some(column) = state.glossary_term.take() {
+                // Marker and term become the first line's lead, padded out
+                // to the definition column
+                let lead = std::mem::take(&mut state.current_line);
+                state.hang += column + 2;
+                let pad = state.hang.saturating_sub(style::visible_len(&lead)).max(1);
+                state.line_lead = Some(format!("{}{}", lead, " ".repeat(pad)));
+                state.glossary_dash = true;
+            }
         }
         TagEnd::Strikethrough => {
-            state.strikethrough = false;
+            state.push_marker("~~");
+            state.strikethrough = state.strikethrough.saturating_sub(1);
         }
         TagEnd::Link => {
             if let Some(url) = state.link_url.take() {
+                if state.link_refs {
+                    // Repeated URLs share one footer entry
+                    let n = match state.link_urls.iter().position(|u| *u == url) {
+                        Some(i) => i + 1,
+                        None => {
+                            state.link_urls.push(url);
+                            state.link_urls.len()
+                        }
+                    };
+                    let marker = style::styled(&format!("[{}]", n), &[style::DIM], state.use_color);
+                    if state.in_table_cell {
+                        state.table_cell_buf.push_str(&marker);
+                    } else {
+                        state.current_line.push_str(&marker);
+                    }
+                    return;
+                }
+                state.link_urls.push(url.clone());
                 let url_display = style::styled(
                     &format!(" ({})", url),
                     &[style::DIM],
                     state.use_color,
                 );
-                state.current_line.push_str(&url_display);
+                if state.in_table_cell {
+                    state.table_cell_buf.push_str(&url_display);
+                } else {
+                    state.current_line.push_str(&url_display);
+                }
             }
         }
         TagEnd::Table => {
@@ -500,18 +2311,84 @@ fn handle_end_tag(state: &mut RenderState, tag: &TagEnd) {
 }
 
 fn handle_text(state: &mut RenderState, text: &str) {
+    if let Some(block) = state.code_block.as_mut() {
+        block.source.push_str(text);
+    }
+    if state.in_raw_block {
+        // Verbatim: no border, no wrapping, no styling
+        for line in text.strip_suffix('\n').unwrap_or(text).split('\n') {
+            state.push_line(line);
+        }
+        return;
+    }
+
+    let text = if std::mem::take(&mut state.glossary_dash) {
+        strip_glossary_dash(text).unwrap_or(text)
+    } else {
+        text
+    };
+
     if state.in_code_block {
-        for line in text.split('\n') {
-            let formatted = if state.use_color {
-                format!("{}  │ {}{}", style::DIM, style::RESET, line)
-            } else {
-                format!("  | {}", line)
+        // The block's final newline ends the last line rather than starting a new one
+        let lines: Vec<String> = text
+            .strip_suffix('\n')
+            .unwrap_or(text)
+            .split('\n')
+            // Terminal output keeps its colors (reset per line so they can't
+            // bleed); elsewhere escapes are shown, not obeyed
+            .map(|line| match (state.in_ansi_block, state.use_color) {
+                (true, true) => format!("{}{}", filter_sgr(line, true), style::RESET),
+                (true, false) => filter_sgr(line, false),
+                (false, _) => escape_controls(line),
+            })
+            .collect();
+        // Word diffs pair up lines, so the whole block is highlighted at once
+        let word_diff = (state.word_diff && state.use_color && state.code_lang.is_some_and(|l| l.is_diff()))
+            .then(|| highlight::highlight_word_diff(&lines, &state.theme.added, &state.theme.removed));
+        for (i, line) in lines.into_iter().enumerate() {
+            let formatted = match (state.use_color, line.is_empty()) {
+                (true, true) => format!("{}  {}{}", style::DIM, state.glyphs.bar, style::RESET),
+                (true, false) => {
+                    let code = match (&word_diff, state.code_lang) {
+                        (Some(highlighted), _) => highlighted[i].clone(),
+                        (None, Some(lang)) if lang.is_diff() => {
+                            highlight::highlight_diff_line(&line, &state.theme.added, &state.theme.removed)
+                        }
+                        (None, Some(lang)) => lang.highlight_line(&line),
+                        (None, None) => line,
+                    };
+                    format!("{}  {} {}{}", style::DIM, state.glyphs.bar, style::RESET, code)
+                }
+                (false, true) => "  |".to_string(),
+                (false, false) => format!("  | {}", line),
             };
             state.push_line(&formatted);
         }
         return;
     }
 
+    let substituted;
+    let text = if state.symbols {
+        substituted = substitute_symbols(text);
+        substituted.as_str()
+    } else {
+        text
+    };
+
+    // Unlike code blocks, prose must not carry tabs: they count as one column
+    let expanded;
+    let text = if text.contains('\t') {
+        let col = if state.in_table_cell {
+            style::visible_len(&state.table_cell_buf)
+        } else {
+            style::visible_len(&state.current_line)
+        };
+        expanded = expand_tabs(text, col, state.tab_width);
+        expanded.as_str()
+    } else {
+        text
+    };
+
     if state.in_table_cell {
         state.table_cell_buf.push_str(text);
         return;
@@ -528,14 +2405,60 @@ fn handle_code(state: &mut RenderState, code: &str) {
         state.table_cell_buf.push_str(code);
         return;
     }
-    let styled = if state.use_color {
-        format!("{} {} {}", style::BG_GREY, code, style::RESET)
-    } else {
+    let styled = if state.inline_code_style == InlineCodeStyle::Brackets {
+        let [open, close] = state.glyphs.code_brackets;
+        let prefix = state.current_style_prefix();
+        let reset = if prefix.is_empty() { "" } else { style::RESET };
+        format!("{}{}{}{}{}", prefix, open, code, close, reset)
+    } else if !state.use_color {
         format!("`{}`", code)
+    } else if state.inline_code_style == InlineCodeStyle::Underline {
+        format!("{}{}{}{}", state.current_style_prefix(), style::UNDERLINE, code, style::RESET)
+    } else if state.in_heading.is_some() {
+        // Keep the heading's weight, swapping only its color
+        format!(
+            "{}{}{}{}",
+            state.current_style_prefix(),
+            state.theme.code_fg,
+            code,
+            style::RESET
+        )
+    } else {
+        // Bold or italic around the span carries into it
+        format!(
+            "{}{} {} {}",
+            state.current_style_prefix(),
+            state.theme.code_bg,
+            code,
+            style::RESET
+        )
     };
     state.current_line.push_str(&styled);
 }
 
+/// Draw the open code block's top border (with its language label) and its
+/// bottom border, both spanning the widest line of the block, capped at the
+/// render width.
+fn draw_code_box(state: &mut RenderState) {
+    let Some(block) = state.code_block.as_ref() else {
+        return;
+    };
+    let top = block.lines.start;
+    let g = state.glyphs;
+    let label = if block.lang.is_empty() {
+        format!("  {}{}", g.code_top, g.rule)
+    } else {
+        format!("  {}{} {} ", g.code_top, g.rule, block.lang)
+    };
+    let widest = state.lines[top + 1..].iter().map(|l| style::visible_len(l)).max().unwrap_or(0);
+    let box_width = widest.min(state.width).max(style::visible_len(&label) + 2);
+
+    let top_border = format!("{}{}", label, g.rule.repeat(box_width - style::visible_len(&label)));
+    let bottom_border = format!("  {}{}", g.code_bottom, g.rule.repeat(box_width.saturating_sub(3)));
+    state.lines[top] = style::styled(&top_border, &[style::DIM], state.use_color);
+    state.push_line(&style::styled(&bottom_border, &[style::DIM], state.use_color));
+}
+
 fn handle_soft_break(state: &mut RenderState) {
     if !state.in_code_block {
         state.current_line.push(' ');
@@ -543,42 +2466,168 @@ fn handle_soft_break(state: &mut RenderState) {
 }
 
 fn handle_hard_break(state: &mut RenderState) {
+    if state.in_table_cell {
+        state.table_cell_buf.push(CELL_BREAK);
+        return;
+    }
     state.flush_wrapped();
 }
 
 fn handle_rule(state: &mut RenderState) {
+    state.last_paragraph = None;
     state.push_blank();
-    let rule: String = "─".repeat(state.width);
+    let rule: String = state.glyphs.rule.repeat(state.text_width);
     let styled_rule = style::styled(&rule, &[style::DIM], state.use_color);
     state.push_line(&styled_rule);
     state.push_blank();
 }
 
-fn handle_task_marker(state: &mut RenderState, checked: bool) {
-    let marker = if checked {
-        style::styled("[✓]", &[style::FG_GREEN, style::BOLD], state.use_color)
+/// Display math on lines of its own, centered: laid out in two dimensions
+/// when [`math::layout`] understands it, otherwise as the LaTeX source.
+fn handle_display_math(state: &mut RenderState, source: &str) {
+    let raw = format!("$${}$$", source);
+    if state.in_table_cell {
+        handle_text(state, &raw);
+        return;
+    }
+    state.flush_wrapped();
+    let prefix = format!("{}{}", state.indent_prefix(), " ".repeat(state.hang));
+    let available = state.text_width.saturating_sub(style::visible_len(&prefix));
+    // The layout leans on box-drawing and Greek characters, so ASCII output keeps the source
+    let rows = Some(source)
+        .filter(|_| state.glyphs == Glyphs::UNICODE)
+        .and_then(|source| math::layout(source, state.glyphs.rule))
+        .filter(|rows| rows.iter().all(|row| style::visible_len(row) <= available));
+    match rows {
+        Some(rows) => {
+            let width = rows.iter().map(|row| style::visible_len(row)).max().unwrap_or(0);
+            let pad = " ".repeat((available - width) / 2);
+            for row in rows {
+                state.push_line(&format!("{}{}{}", prefix, pad, row));
+            }
+        }
+        None => {
+            for line in raw.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                state.push_line(&format!("{}{}", prefix, line));
+            }
+        }
+    }
+}
+
+/// The text inside `<!-- ... -->`, or `None` if `html` is not a comment.
+fn comment_text(html: &str) -> Option<&str> {
+    html.trim().strip_prefix("<!--")?.strip_suffix("-->")
+}
+
+/// Without color a note keeps its delimiters, so it still reads as a comment.
+fn comment_note(state: &RenderState, text: &str) -> String {
+    if state.use_color {
+        style::styled(text, &[style::DIM, style::ITALIC], true)
     } else {
-        style::styled("[ ]", &[style::DIM], state.use_color)
-    };
-    state.current_line.push_str(&format!("{} ", marker));
+        text.to_string()
+    }
 }
 
-fn render_table(state: &mut RenderState) {
-    if state.table_rows.is_empty() {
+fn handle_inline_html(state: &mut RenderState, html: &str) {
+    let Some(inner) = comment_text(html).filter(|_| state.show_comments) else {
         return;
+    };
+    let text = if state.use_color {
+        inner.split_whitespace().collect::<Vec<_>>().join(" ")
+    } else {
+        html.split_whitespace().collect::<Vec<_>>().join(" ")
+    };
+    let note = comment_note(state, &text);
+    if state.in_table_cell {
+        state.table_cell_buf.push_str(&note);
+    } else {
+        state.current_line.push_str(&note);
     }
+}
 
-    let num_cols = state.table_rows.iter().map(|r| r.len()).max().unwrap_or(0);
-    if num_cols == 0 {
+/// A block-level comment becomes a note paragraph, one output line per comment line.
+fn handle_comment_block(state: &mut RenderState, html: &str) {
+    let Some(inner) = comment_text(html).filter(|_| state.show_comments) else {
         return;
+    };
+    let text = if state.use_color { inner } else { html.trim() };
+    state.push_blank();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        state.current_line = comment_note(state, line);
+        state.flush_wrapped();
     }
+}
+
+fn footnote_marker(state: &RenderState, label: &str) -> String {
+    style::styled(&format!("[{}]", label), &[&state.theme.accent], state.use_color)
+}
+
+/// Footnote references attach directly to the preceding text, so wrapping keeps
+/// `word[1]` together as one segment.
+fn handle_footnote_reference(state: &mut RenderState, label: &str) {
+    state.footnote_refs.push((label.to_string(), state.lines.len()));
+    let marker = footnote_marker(state, label);
+    if state.in_table_cell {
+        state.table_cell_buf.push_str(&marker);
+    } else {
+        state.current_line.push_str(&marker);
+    }
+}
+
+fn handle_task_marker(state: &mut RenderState, checked: bool) {
+    state.tasks.0 += checked as usize;
+    state.tasks.1 += 1;
+    let marker = if checked {
+        let check = format!("[{}]", state.glyphs.check);
+        style::styled(&check, &[&state.theme.done, style::BOLD], state.use_color)
+    } else {
+        style::styled("[ ]", &[&state.theme.todo], state.use_color)
+    };
+    // In a bulleted list the checkbox replaces the bullet, so the item's text
+    // lines up with plain items; numbered items keep their number
+    let bullet_only = state.marker_pending
+        && matches!(state.list_stack.last(), Some(ListContext::Unordered(_)))
+        && style::visible_len(&state.current_line) == state.hang;
+    if bullet_only {
+        state.current_line.clear();
+        state.task_box = Some(marker);
+    } else {
+        state.current_line.push_str(&format!("{} ", marker));
+    }
+}
+
+/// The first-line prefix of a task item: `base` with the checkbox ending
+/// where the bullet would have, its extra width taken from the indent.
+fn outdent_task_box(base: &str, checkbox: &str, hang: usize) -> String {
+    let width = style::visible_len(checkbox) + 1;
+    let trailing = base.len() - base.trim_end_matches(' ').len();
+    let outdent = width.saturating_sub(hang).min(trailing);
+    format!(
+        "{}{} {}",
+        &base[..base.len() - outdent],
+        checkbox,
+        " ".repeat((hang + outdent).saturating_sub(width))
+    )
+}
+
+fn render_table(state: &mut RenderState) {
+    if state.table_rows.is_empty() {
+        return;
+    }
+
+    let num_cols = state.table_rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    if num_cols == 0 {
+        return;
+    }
+    let glyphs = state.glyphs;
 
     // Calculate natural column widths using visible length (ignoring ANSI codes)
     let mut col_widths = vec![0usize; num_cols];
     for row in &state.table_rows {
         for (i, cell) in row.iter().enumerate() {
             if i < num_cols {
-                col_widths[i] = col_widths[i].max(style::visible_len(cell));
+                let widest = cell.split(CELL_BREAK).map(style::visible_len).max().unwrap_or(0);
+                col_widths[i] = col_widths[i].max(widest);
             }
         }
     }
@@ -588,13 +2637,19 @@ fn render_table(state: &mut RenderState) {
     }
 
     // Shrink columns to fit within terminal width
-    // Table overhead: 2 (margin) + 1 (left border) + 1 (right border) + 2*num_cols (spaces) + (num_cols-1) (mid borders)
+    // Grid overhead: 2 (margin) + 1 (left border) + 1 (right border) + 2*num_cols (spaces) + (num_cols-1) (mid borders)
     // = 3 + 3*num_cols
-    let overhead = 3 + 3 * num_cols;
+    // Simple/minimal overhead: 2 (margin) + 2*(num_cols-1) (gutters) = 2*num_cols
+    let table_style = state.table_style;
+    let overhead = match table_style {
+        TableStyle::Grid => 3 + 3 * num_cols,
+        TableStyle::Simple | TableStyle::Minimal => 2 * num_cols,
+    };
     let max_content = state.width.saturating_sub(overhead);
     let total: usize = col_widths.iter().sum();
 
-    if total > max_content && max_content > 0 {
+    if total > max_content {
+        let natural = total + overhead;
         let min_col_width = 3usize;
         let guaranteed = min_col_width * num_cols;
 
@@ -643,9 +2698,18 @@ fn render_table(state: &mut RenderState) {
                 *w = (*w).max(min_col_width);
             }
         }
+
+        let fit = TableFit {
+            line: state.lines.len(),
+            natural,
+            fitted: col_widths.iter().sum::<usize>() + overhead,
+        };
+        crate::info!("table at line {} {}", state.lines.len() + 1, fit.describe());
+        state.squeezed_tables.push(fit);
     }
 
     let no_wrap = state.no_wrap;
+    let truncate = state.truncate;
     let use_color = state.use_color;
 
     let draw_separator = |state: &mut RenderState, left: &str, mid: &str, right: &str, fill: &str| {
@@ -660,13 +2724,55 @@ fn render_table(state: &mut RenderState) {
         state.push_line(&style::styled(&line, &[style::DIM], state.use_color));
     };
 
-    draw_separator(state, "┌", "┬", "┐", "─");
+    // Header underline for the simple style: one dash run per column
+    let draw_underline = |state: &mut RenderState| {
+        let dashes: Vec<String> = col_widths.iter().map(|w| glyphs.rule.repeat(*w)).collect();
+        let line = format!("  {}", dashes.join("  "));
+        state.push_line(&style::styled(&line, &[style::DIM], state.use_color));
+    };
+
+    // Cell delimiters (left edge, between cells, right edge) for each style
+    let (left_border, mid_border, right_border) = match table_style {
+        TableStyle::Grid if use_color => (
+            format!("  {}{}{} ", style::DIM, glyphs.bar, style::RESET),
+            format!(" {}{}{} ", style::DIM, glyphs.bar, style::RESET),
+            format!(" {}{}{}", style::DIM, glyphs.bar, style::RESET),
+        ),
+        TableStyle::Grid => ("  | ".to_string(), " | ".to_string(), " |".to_string()),
+        TableStyle::Simple | TableStyle::Minimal => ("  ".to_string(), "  ".to_string(), String::new()),
+    };
+
+    if let Some(caption) = state.table_caption.take() {
+        let table_width = match table_style {
+            TableStyle::Grid => col_widths.iter().sum::<usize>() + 3 * num_cols + 1,
+            TableStyle::Simple | TableStyle::Minimal => {
+                col_widths.iter().sum::<usize>() + 2 * (num_cols - 1)
+            }
+        };
+        let caption = caption.trim();
+        let pad = table_width.saturating_sub(style::visible_len(caption)) / 2;
+        state.push_line(&format!("  {}{}", " ".repeat(pad), caption));
+    }
+
+    if table_style == TableStyle::Grid {
+        let [left, mid, right] = glyphs.table[0];
+        draw_separator(state, left, mid, right, glyphs.rule);
+    }
 
-    for (row_idx, row) in state.table_rows.clone().iter().enumerate() {
+    let draw_header_rule = |state: &mut RenderState| match table_style {
+        TableStyle::Grid => {
+            let [left, mid, right] = glyphs.table[1];
+            draw_separator(state, left, mid, right, glyphs.rule)
+        }
+        TableStyle::Simple => draw_underline(state),
+        TableStyle::Minimal => {}
+    };
+
+    let emit_row = |state: &mut RenderState, row_idx: usize, row: &[String]| {
         // Wrap each cell to its allocated column width
         let mut wrapped_cells: Vec<Vec<String>> = row.iter().enumerate().map(|(i, cell)| {
             let w = col_widths.get(i).copied().unwrap_or(3);
-            wrap_cell_text(cell, w, no_wrap, use_color)
+            wrap_cell_text(cell, w, no_wrap, truncate, &glyphs, use_color)
         }).collect();
 
         // Pad to num_cols if row has fewer cells
@@ -679,12 +2785,7 @@ fn render_table(state: &mut RenderState) {
 
         // Emit each sub-line of the row
         for line_idx in 0..max_lines {
-            let mut line = String::new();
-            if use_color {
-                line.push_str(&format!("  {}│{} ", style::DIM, style::RESET));
-            } else {
-                line.push_str("  | ");
-            }
+            let mut line = left_border.clone();
 
             for (i, cell_lines) in wrapped_cells.iter().enumerate() {
                 let w = col_widths.get(i).copied().unwrap_or(3);
@@ -706,28 +2807,33 @@ fn render_table(state: &mut RenderState) {
                 line.push_str(&styled_cell);
 
                 if i < num_cols - 1 {
-                    if use_color {
-                        line.push_str(&format!(" {}│{} ", style::DIM, style::RESET));
-                    } else {
-                        line.push_str(" | ");
-                    }
+                    line.push_str(&mid_border);
                 }
             }
 
-            if use_color {
-                line.push_str(&format!(" {}│{}", style::DIM, style::RESET));
-            } else {
-                line.push_str(" |");
-            }
+            line.push_str(&right_border);
             state.push_line(&line);
         }
+    };
 
+    let rows = std::mem::take(&mut state.table_rows);
+    let repeat = state.table_header_repeat;
+    for (row_idx, row) in rows.iter().enumerate() {
+        // Row 0 is the header; repeat it before data rows 1 + N, 1 + 2N, ...
+        if repeat > 0 && row_idx > 1 && (row_idx - 1) % repeat == 0 {
+            emit_row(state, 0, &rows[0]);
+            draw_header_rule(state);
+        }
+        emit_row(state, row_idx, row);
         if row_idx == 0 {
-            draw_separator(state, "├", "┼", "┤", "─");
+            draw_header_rule(state);
         }
     }
 
-    draw_separator(state, "└", "┴", "┘", "─");
+    if table_style == TableStyle::Grid {
+        let [left, mid, right] = glyphs.table[2];
+        draw_separator(state, left, mid, right, glyphs.rule);
+    }
 
     state.table_rows.clear();
     state.table_alignments.clear();
@@ -738,17 +2844,25 @@ mod tests {
     use super::*;
     use crate::parser;
 
+    fn opts(use_color: bool, no_wrap: bool) -> RenderOptions {
+        RenderOptions {
+            use_color,
+            no_wrap,
+            ..RenderOptions::default()
+        }
+    }
+
     #[test]
     fn test_heading_renders() {
         let events = parser::parse("# Hello World");
-        let lines = render(events, 80, false, false);
+        let lines = render(events, 80, &opts(false, false));
         assert!(lines.iter().any(|l| l.contains("Hello World")));
     }
 
     #[test]
     fn test_bold_renders() {
         let events = parser::parse("**bold text**");
-        let lines = render(events, 80, true, false);
+        let lines = render(events, 80, &opts(true, false));
         let joined = lines.join("");
         assert!(joined.contains("bold text"));
     }
@@ -756,7 +2870,7 @@ mod tests {
     #[test]
     fn test_inline_formatting_stays_on_one_line() {
         let events = parser::parse("This is **bold** and *italic* text.");
-        let lines = render(events, 80, false, false);
+        let lines = render(events, 80, &opts(false, false));
         let content_lines: Vec<&String> = lines.iter().filter(|l| !l.is_empty()).collect();
         assert_eq!(content_lines.len(), 1, "Expected 1 content line, got: {:?}", content_lines);
         assert!(content_lines[0].contains("bold"));
@@ -766,7 +2880,7 @@ mod tests {
     #[test]
     fn test_list_renders() {
         let events = parser::parse("- item one\n- item two");
-        let lines = render(events, 80, false, false);
+        let lines = render(events, 80, &opts(false, false));
         assert!(lines.iter().any(|l| l.contains("item one")));
         assert!(lines.iter().any(|l| l.contains("item two")));
     }
@@ -774,21 +2888,21 @@ mod tests {
     #[test]
     fn test_code_block_renders() {
         let events = parser::parse("```\nlet x = 1;\n```");
-        let lines = render(events, 80, false, false);
+        let lines = render(events, 80, &opts(false, false));
         assert!(lines.iter().any(|l| l.contains("let x = 1;")));
     }
 
     #[test]
     fn test_horizontal_rule() {
         let events = parser::parse("---");
-        let lines = render(events, 40, false, false);
+        let lines = render(events, 40, &opts(false, false));
         assert!(lines.iter().any(|l| l.contains("─")));
     }
 
     #[test]
     fn test_word_wrap() {
         let events = parser::parse("This is a very long line that should be wrapped when the terminal width is narrow enough to require it.");
-        let lines = render(events, 40, false, false);
+        let lines = render(events, 40, &opts(false, false));
         let content_lines: Vec<&String> = lines.iter().filter(|l| !l.is_empty()).collect();
         assert!(content_lines.len() > 1, "Long text should wrap");
         for line in &content_lines {
@@ -799,7 +2913,7 @@ mod tests {
     #[test]
     fn test_no_wrap_truncates_with_ellipsis() {
         let events = parser::parse("This is a very long line that should be truncated when no-wrap mode is enabled.");
-        let lines = render(events, 30, false, true);
+        let lines = render(events, 30, &opts(false, true));
         let content_lines: Vec<&String> = lines.iter().filter(|l| !l.is_empty()).collect();
         assert_eq!(content_lines.len(), 1, "No-wrap should produce one line");
         assert!(content_lines[0].ends_with('…'), "Truncated line should end with ellipsis");
@@ -810,7 +2924,7 @@ mod tests {
     fn test_table_respects_terminal_width() {
         let md = "| Short | This column has a very long text that should cause wrapping when the terminal is narrow enough |\n|---|---|\n| a | More long text that definitely exceeds the terminal width for testing purposes |";
         let events = parser::parse(md);
-        let lines = render(events, 50, false, false);
+        let lines = render(events, 50, &opts(false, false));
         for line in &lines {
             assert!(
                 style::visible_len(line) <= 50,
@@ -825,7 +2939,7 @@ mod tests {
     fn test_table_multiline_cells() {
         let md = "| A | B |\n|---|---|\n| short | This is a long cell that should definitely wrap to multiple lines within the table row |\n";
         let events = parser::parse(md);
-        let lines = render(events, 40, false, false);
+        let lines = render(events, 40, &opts(false, false));
         // Count data row lines (lines with | between header separator and bottom border)
         let table_lines: Vec<&String> = lines.iter().filter(|l| l.contains('|')).collect();
         // Should have: header + at least 2 lines for the data row (since cell wraps)
@@ -834,17 +2948,1361 @@ mod tests {
 
     #[test]
     fn test_wrap_cell_text_basic() {
-        let lines = wrap_cell_text("short text", 20, false, false);
+        let lines = wrap_cell_text("short text", 20, false, TruncatePosition::End, &Glyphs::UNICODE, false);
         assert_eq!(lines.len(), 1);
         assert_eq!(lines[0], "short text");
     }
 
     #[test]
     fn test_wrap_cell_text_wraps() {
-        let lines = wrap_cell_text("this is a longer text that needs wrapping", 15, false, false);
+        let lines = wrap_cell_text("this is a longer text that needs wrapping", 15, false, TruncatePosition::End, &Glyphs::UNICODE, false);
         assert!(lines.len() > 1, "Should wrap: {:?}", lines);
         for line in &lines {
             assert!(style::visible_len(line) <= 15, "Wrapped line too wide: '{}'", line);
         }
     }
+
+    #[test]
+    fn test_table_style_simple_has_no_vertical_bars() {
+        let md = "| Name | Value |\n|---|---|\n| alpha | 1 |\n| b | 22 |";
+        let events = parser::parse(md);
+        let options = RenderOptions {
+            table_style: TableStyle::Simple,
+            ..opts(true, false)
+        };
+        let lines = render(events, 80, &options);
+        assert!(lines.iter().all(|l| !l.contains('│')), "Simple table should have no bars: {:?}", lines);
+        assert!(lines.iter().any(|l| l.contains('─')), "Simple table should underline the header");
+
+        // Second column starts at the same visible offset in every row
+        let plain: Vec<String> = render(parser::parse(md), 80, &RenderOptions {
+            table_style: TableStyle::Simple,
+            ..opts(false, false)
+        });
+        let offsets: Vec<usize> = plain
+            .iter()
+            .filter(|l| l.contains("Value") || l.contains(" 1") || l.contains("22"))
+            .map(|l| {
+                let col = l.find(|c: char| c.is_ascii_digit() || c == 'V').unwrap();
+                l[..col].chars().count()
+            })
+            .collect();
+        assert_eq!(offsets.len(), 3, "Expected header and two rows: {:?}", plain);
+        assert!(offsets.iter().all(|o| *o == offsets[0]), "Columns misaligned: {:?}", plain);
+    }
+
+    #[test]
+    fn test_table_style_minimal_has_no_borders() {
+        let md = "| A | B |\n|---|---|\n| x | y |";
+        let events = parser::parse(md);
+        let options = RenderOptions {
+            table_style: TableStyle::Minimal,
+            ..opts(false, false)
+        };
+        let lines = render(events, 80, &options);
+        for line in &lines {
+            assert!(!line.contains('|') && !line.contains('─'), "Unexpected border: '{}'", line);
+        }
+        assert!(lines.iter().any(|l| l.contains('A') && l.contains('B')));
+    }
+
+    #[test]
+    fn test_table_style_from_str() {
+        assert_eq!("grid".parse::<TableStyle>(), Ok(TableStyle::Grid));
+        assert_eq!("simple".parse::<TableStyle>(), Ok(TableStyle::Simple));
+        assert_eq!("minimal".parse::<TableStyle>(), Ok(TableStyle::Minimal));
+        assert!("fancy".parse::<TableStyle>().is_err());
+    }
+
+    #[test]
+    fn test_raw_fence_renders_verbatim() {
+        let art = "+--+    +--+\n|  |--->|  |\n+--+    +--+";
+        let md = format!("```raw\n{}\n```\n", art);
+        let lines = render(parser::parse(&md), 80, &opts(true, false));
+        let content: Vec<&String> = lines.iter().filter(|l| !l.is_empty()).collect();
+        let expected: Vec<&str> = art.lines().collect();
+        assert_eq!(content, expected, "Raw block should render byte-for-byte");
+    }
+
+    #[test]
+    fn test_custom_raw_fence() {
+        let md = "```diagram\n  a  b\n```\n";
+        let options = RenderOptions {
+            raw_fences: vec!["diagram".to_string()],
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse(md), 80, &options);
+        assert!(lines.iter().any(|l| l == "  a  b"));
+        assert!(lines.iter().all(|l| !l.contains('╭')));
+    }
+
+    #[test]
+    fn test_inline_code_adjacent_text_keeps_distinct_styling() {
+        let lines = render(parser::parse("`foo`bar"), 80, &opts(true, false));
+        let line = lines.iter().find(|l| l.contains("foo")).unwrap();
+        assert!(line.contains(style::BG_GREY));
+        assert!(
+            line.contains(&format!("foo{}", style::RESET)),
+            "Code background should end with the code text: {:?}",
+            line
+        );
+        let code_end = format!("foo{}", style::RESET);
+        let after_reset = line.find(&code_end).unwrap() + code_end.len();
+        let bar = line.find("bar").unwrap();
+        assert!(!line[after_reset..bar].contains('\x1b'), "No styling should precede 'bar': {:?}", line);
+    }
+
+    #[test]
+    fn test_inline_code_reset_stays_with_code_when_wrapping() {
+        let words = split_styled_words(&format!("{} foo {}bar", style::BG_GREY, style::RESET));
+        assert_eq!(words, vec![format!("{}foo{}", style::BG_GREY, style::RESET), "bar".to_string()]);
+    }
+
+    #[test]
+    fn test_code_span_with_literal_backticks() {
+        // Double-backtick spans may contain single backticks
+        for (input, code) in [("``a`b`` end", "a`b"), ("`` ` `` end", "`"), ("`plain` end", "plain")] {
+            let lines = render(parser::parse(input), 80, &opts(false, false));
+            let line = lines.iter().find(|l| l.contains("end")).unwrap();
+            assert_eq!(line, &format!("`{}` end", code));
+
+            let lines = render(parser::parse(input), 80, &opts(true, false));
+            let line = lines.iter().find(|l| l.contains("end")).unwrap();
+            assert!(
+                line.starts_with(&format!("{}{}{}", style::BG_GREY, code, style::RESET)),
+                "Got: {:?}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_list_inside_blockquote_has_bar_and_bullet() {
+        for use_color in [false, true] {
+            let lines = render(parser::parse("> - a\n> - b"), 80, &opts(use_color, false));
+            let bar = if use_color { '│' } else { '|' };
+            for item in ["a", "b"] {
+                let line = lines
+                    .iter()
+                    .find(|l| l.trim_end_matches(style::RESET).ends_with(item))
+                    .unwrap_or_else(|| panic!("Missing item {}: {:?}", item, lines));
+                assert_eq!(line.matches(bar).count(), 1, "Expected one bar: {:?}", line);
+                assert!(line.contains('•'), "Expected a bullet: {:?}", line);
+                assert!(line.find(bar) < line.find('•'), "Bar should precede bullet: {:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parent_item_keeps_its_indent_before_nested_list() {
+        let lines = render(parser::parse("- a\n- b\n  - c"), 80, &opts(false, false));
+        assert!(lines.iter().any(|l| l == "  • b"), "Got: {:?}", lines);
+        assert!(lines.iter().any(|l| l == "    ◦ c"), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_no_wrap_list_item_indented_once() {
+        let lines = render(parser::parse("- item"), 80, &opts(false, true));
+        assert!(lines.iter().any(|l| l == "  • item"), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_middle_truncation_keeps_both_ends() {
+        let md = "/very/long/path/to/some/deeply/nested/directory/structure/file.md";
+        let options = RenderOptions {
+            truncate: TruncatePosition::Middle,
+            ..opts(false, true)
+        };
+        let lines = render(parser::parse(md), 30, &options);
+        let line = lines.iter().find(|l| !l.is_empty()).unwrap();
+        assert!(line.starts_with("/very/long"), "Should keep the start: {:?}", line);
+        assert!(line.ends_with("file.md"), "Should keep the end: {:?}", line);
+        assert!(line.contains('…'));
+        assert!(style::visible_len(line) <= 30, "Too wide: {:?}", line);
+    }
+
+    #[test]
+    fn test_middle_truncation_is_ansi_aware() {
+        let text = format!("{}abcdefghij{}klmnopqrst{}", style::BOLD, style::ITALIC, style::RESET);
+        let truncated = truncate_styled_at(&text, 7, TruncatePosition::Middle, &Glyphs::UNICODE, true);
+        assert_eq!(style::visible_len(&truncated), 7);
+        assert!(truncated.starts_with(&format!("{}abc", style::BOLD)));
+        assert!(truncated.contains(&format!("{}rst", style::ITALIC)));
+    }
+
+    #[test]
+    fn test_start_truncation_keeps_end() {
+        let truncated = truncate_styled_at("0123456789", 5, TruncatePosition::Start, &Glyphs::UNICODE, false);
+        assert_eq!(truncated, "…6789");
+    }
+
+    #[test]
+    fn test_render_document_locates_links() {
+        let md = "See [one](http://a.example) and\n[two](http://b.example).\n\n[three](http://c.example)";
+        let doc = render_document(parser::parse(md), 80, &opts(true, false));
+        let urls: Vec<&str> = doc.links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(urls, vec!["http://a.example", "http://b.example", "http://c.example"]);
+        for link in &doc.links {
+            let plain: String = strip_for_test(&doc.lines[link.line]);
+            let text: String = plain.chars().skip(link.start).take(link.end - link.start).collect();
+            assert_eq!(text, link.url);
+        }
+    }
+
+    fn strip_for_test(s: &str) -> String {
+        let mut out = String::new();
+        let mut in_escape = false;
+        for ch in s.chars() {
+            if in_escape {
+                in_escape = !ch.is_ascii_alphabetic();
+            } else if ch == '\x1b' {
+                in_escape = true;
+            } else {
+                out.push(ch);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_setext_h1_styled_as_heading() {
+        let lines = render(parser::parse("Title\n=====\n\nBody"), 80, &opts(true, false));
+        let title = lines.iter().find(|l| l.contains("Title")).unwrap();
+        assert!(title.starts_with(&style::combine(&[style::BOLD, style::UNDERLINE, style::FG_BRIGHT_WHITE])));
+        assert!(!lines.iter().any(|l| l.contains("===")));
+    }
+
+    #[test]
+    fn test_setext_h2_styled_as_heading_not_rule() {
+        let lines = render(parser::parse("Intro\n\nSubtitle\n--------\nBody"), 80, &opts(true, false));
+        let subtitle = lines.iter().find(|l| l.contains("Subtitle")).unwrap();
+        assert!(subtitle.starts_with(&style::combine(&[style::BOLD, style::FG_BRIGHT_CYAN])));
+        assert!(!lines.iter().any(|l| l.contains('─')), "Setext underline rendered as a rule: {:?}", lines);
+    }
+
+    #[test]
+    fn test_block_spacing_zero_is_compact() {
+        let options = RenderOptions {
+            block_spacing: 0,
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse("First paragraph.\n\nSecond paragraph."), 80, &options);
+        assert_eq!(lines, vec!["First paragraph.", "Second paragraph."]);
+    }
+
+    #[test]
+    fn test_block_spacing_two() {
+        let options = RenderOptions {
+            block_spacing: 2,
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse("One.\n\nTwo."), 80, &options);
+        let one = lines.iter().position(|l| l == "One.").unwrap();
+        assert_eq!(&lines[one + 1..one + 4], &["", "", "Two."]);
+    }
+
+    #[test]
+    fn test_symbols_in_prose_not_code() {
+        let options = RenderOptions {
+            symbols: true,
+            ..opts(false, false)
+        };
+        let md = "a -> b <= c\n\n`x -> y`\n\n```\nf -> g\n```";
+        let lines = render(parser::parse(md), 80, &options);
+        assert!(lines.iter().any(|l| l == "a → b ≤ c"), "Got: {:?}", lines);
+        assert!(lines.iter().any(|l| l.contains("`x -> y`")));
+        assert!(lines.iter().any(|l| l.contains("f -> g")));
+    }
+
+    #[test]
+    fn test_symbols_off_by_default() {
+        let lines = render(parser::parse("a -> b"), 80, &opts(false, false));
+        assert!(lines.iter().any(|l| l == "a -> b"));
+    }
+
+    #[test]
+    fn test_substitute_symbols() {
+        assert_eq!(substitute_symbols("x => y != z >= w <- v <=> u"), "x ⇒ y ≠ z ≥ w ← v ⇔ u");
+    }
+
+    #[test]
+    fn test_fence_info_parse() {
+        let info = FenceInfo::parse("rust,ignore");
+        assert_eq!(info.lang, "rust");
+        assert_eq!(info.attrs, vec!["ignore"]);
+
+        let info = FenceInfo::parse("rust {.line-numbers}");
+        assert_eq!(info.lang, "rust");
+        assert!(info.has_attr("line-numbers"));
+
+        assert_eq!(FenceInfo::parse(""), FenceInfo::default());
+    }
+
+    #[test]
+    fn test_code_block_label_uses_language_only() {
+        let lines = render(parser::parse("```rust,ignore\nfn main() {}\n```"), 80, &opts(false, false));
+        assert!(lines.iter().any(|l| l.trim_end_matches('─').trim() == "╭─ rust"), "Got: {:?}", lines);
+        assert!(!lines.iter().any(|l| l.contains("ignore")));
+    }
+
+    #[test]
+    fn test_deep_list_nesting_fits_narrow_width() {
+        let md: String = (0..10)
+            .map(|depth| format!("{}- item {}\n", "  ".repeat(depth), depth))
+            .collect();
+        let lines = render(parser::parse(&md), 20, &opts(false, false));
+        for line in &lines {
+            assert!(style::visible_len(line) <= 20, "Line exceeds width: {:?}", line);
+        }
+        assert!(lines.iter().any(|l| l.contains("▸ item 9")), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_keep_markers_without_color() {
+        let options = RenderOptions {
+            keep_markers: true,
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse("Some **bold**, *italic* and ~~gone~~ text."), 80, &options);
+        assert!(
+            lines.iter().any(|l| l == "Some *bold*, _italic_ and ~~gone~~ text."),
+            "Got: {:?}",
+            lines
+        );
+    }
+
+    #[test]
+    fn test_keep_markers_ignored_with_color() {
+        let options = RenderOptions {
+            keep_markers: true,
+            ..opts(true, false)
+        };
+        let lines = render(parser::parse("**bold**"), 80, &options);
+        assert!(!lines.iter().any(|l| l.contains('*')));
+    }
+
+    #[test]
+    fn test_head_truncates_with_marker() {
+        let lines: Vec<String> = (0..20).map(|i| format!("line {}", i)).collect();
+        let cut = head(lines.clone(), 5, &Glyphs::UNICODE, false);
+        assert_eq!(cut.len(), 6);
+        assert_eq!(cut[5], "… (truncated)");
+        assert_eq!(head(lines[..3].to_vec(), 5, &Glyphs::UNICODE, false).len(), 3);
+    }
+
+    #[test]
+    fn test_code_block_blank_line_has_clean_bar() {
+        let md = "```\nfirst\n\nsecond\n```";
+        let lines = render(parser::parse(md), 80, &opts(false, false));
+        let first = lines.iter().position(|l| l.ends_with("first")).unwrap();
+        assert_eq!(lines[first + 1], "  |");
+        assert!(lines[first + 2].ends_with("second"));
+        // No stray empty bar line before the bottom border
+        assert!(lines[first + 3].contains('╰'), "Got: {:?}", lines);
+
+        let colored = render(parser::parse(md), 80, &opts(true, false));
+        let first = colored
+            .iter()
+            .position(|l| l.trim_end_matches(style::RESET).ends_with("first"))
+            .unwrap();
+        assert_eq!(colored[first + 1], format!("{}  │{}", style::DIM, style::RESET));
+    }
+
+    #[test]
+    fn test_footnote_marker_stays_with_word() {
+        let md = "Some words here that will need wrapping near the end word[^1] and more.\n\n[^1]: The note.";
+        for width in 20..40 {
+            let lines = render(parser::parse(md), width, &opts(false, false));
+            let def = lines.iter().position(|l| l.starts_with("[1] The note.")).unwrap();
+            let refs: Vec<&String> = lines[..def].iter().filter(|l| l.contains("[1]")).collect();
+            assert_eq!(refs.len(), 1, "width {}: {:?}", width, lines);
+            assert!(refs[0].contains("word[1]"), "width {}: marker split from word: {:?}", width, lines);
+        }
+    }
+
+    #[test]
+    fn test_code_block_highlighted_by_language() {
+        let lines = render(parser::parse("```rust,ignore\nfn main() {}\n```"), 80, &opts(true, false));
+        let code = lines.iter().find(|l| l.contains("main")).unwrap();
+        assert!(code.contains(&format!("{}fn{}", style::FG_MAGENTA, style::RESET)), "Got: {:?}", code);
+
+        let plain = render(parser::parse("```\nfn main() {}\n```"), 80, &opts(true, false));
+        let code = plain.iter().find(|l| l.contains("main")).unwrap();
+        assert!(!code.contains(style::FG_MAGENTA));
+    }
+
+    #[test]
+    fn test_nested_blockquote_bars_colored_by_level() {
+        let lines = render(parser::parse("> outer\n>\n> > inner"), 80, &opts(true, false));
+        let outer = lines.iter().find(|l| l.contains("outer")).unwrap();
+        let inner = lines.iter().find(|l| l.contains("inner")).unwrap();
+        let bars = Theme::default().quote_bars;
+        let level1 = format!("{}│", bars[0]);
+        let level2 = format!("{}│", bars[1]);
+        assert_ne!(bars[0], bars[1]);
+        assert!(outer.contains(&level1) && !outer.contains(&level2), "Got: {:?}", outer);
+        assert!(inner.contains(&level1) && inner.contains(&level2), "Got: {:?}", inner);
+    }
+
+    #[test]
+    fn test_nested_blockquote_plain_bars() {
+        let lines = render(parser::parse("> a\n>\n> > b\n>\n> c"), 80, &opts(false, false));
+        assert!(lines.iter().any(|l| l == "  | a"), "Got: {:?}", lines);
+        assert!(lines.iter().any(|l| l == "  | | b"), "Got: {:?}", lines);
+        assert!(lines.iter().any(|l| l == "  | c"), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_fold_hard_wraps_code_lines() {
+        let input = "```\nabcdefghijklmnopqrstuvwxyz0123456789\n```";
+        let lines = fold(render(parser::parse(input), 20, &opts(false, false)), 20);
+        assert!(lines.iter().all(|l| l.chars().count() <= 20), "Got: {:?}", lines);
+        assert!(lines.contains(&"  | abcdefghijklmnop".to_string()), "Got: {:?}", lines);
+        assert!(lines.contains(&"  | qrstuvwxyz012345".to_string()), "Got: {:?}", lines);
+        assert!(lines.contains(&"  | 6789".to_string()), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_expand_tabs_to_next_stop() {
+        assert_eq!(expand_tabs("a\tb", 0, 4), "a   b");
+        assert_eq!(expand_tabs("\tb", 2, 4), "  b");
+        assert_eq!(expand_tabs("ab", 0, 4), "ab");
+    }
+
+    #[test]
+    fn test_prose_tab_expanded_before_wrapping() {
+        let input = "alpha\tbeta gamma delta epsilon zeta eta theta iota kappa";
+        let lines = render(parser::parse(input), 22, &opts(false, false));
+        assert!(lines.iter().all(|l| !l.contains('\t')), "Got: {:?}", lines);
+        assert!(lines.iter().all(|l| style::visible_len(l) <= 20), "Got: {:?}", lines);
+        assert!(lines.iter().any(|l| l.contains("alpha beta")), "Got: {:?}", lines);
+
+        // Without wrapping the expanded width decides where the line is cut
+        let lines = render(parser::parse("ab\tcdefghijklmnopqrstuvwxyz"), 12, &opts(false, true));
+        let line = lines.iter().find(|l| l.contains("ab")).unwrap();
+        assert!(!line.contains('\t'), "Got: {:?}", line);
+        assert_eq!(style::visible_len(line), 10, "Got: {:?}", line);
+    }
+
+    #[test]
+    fn test_link_refs_footer_numbers_and_dedupes() {
+        let options = RenderOptions {
+            link_refs: true,
+            ..opts(false, false)
+        };
+        let input = "[a](http://a.example) [b](http://b.example) [again](http://a.example)";
+        let doc = render_document(parser::parse(input), 80, &options);
+        assert!(doc.lines.iter().any(|l| l.contains("a[1] b[2] again[1]")), "Got: {:?}", doc.lines);
+        assert!(doc.lines.iter().any(|l| l == "[1] http://a.example"), "Got: {:?}", doc.lines);
+        assert!(doc.lines.iter().any(|l| l == "[2] http://b.example"), "Got: {:?}", doc.lines);
+        assert_eq!(doc.links.len(), 2);
+        assert_eq!(doc.links[1].start, 4);
+    }
+
+    #[test]
+    fn test_link_refs_wrapped_url_starting_with_bracket() {
+        let options = RenderOptions {
+            link_refs: true,
+            ..opts(false, false)
+        };
+        // The URL wraps right before a `[`, which mustn't read as a new entry
+        let input = "[a](http://example.com/path/abcd[x]/more)";
+        let doc = render_document(parser::parse(input), 20, &options);
+        assert_eq!(doc.links.len(), 1);
+        let footer: Vec<&String> = doc.lines.iter().skip_while(|l| *l != "References").collect();
+        assert_eq!(footer[1..], ["[1] http://example", "    .com/path/abcd", "    [x]/more"], "Got: {:?}", doc.lines);
+    }
+
+    #[test]
+    fn test_link_refs_marker_in_table_cell() {
+        let options = RenderOptions {
+            link_refs: true,
+            ..opts(false, false)
+        };
+        let input = "| Site |\n|------|\n| [home](http://a.example) |\n";
+        let doc = render_document(parser::parse(input), 40, &options);
+        let row = doc.lines.iter().find(|l| l.contains("home")).unwrap();
+        assert!(row.contains("home[1]"), "Got: {:?}", doc.lines);
+        assert!(doc.lines.iter().any(|l| l == "[1] http://a.example"), "Got: {:?}", doc.lines);
+    }
+
+    #[test]
+    fn test_link_refs_long_url_hanging_indent() {
+        let urls: Vec<String> = (1..=10)
+            .map(|i| format!("http://e.com/{}", i))
+            .chain(["http://example.com/a/very/long/path/that/overflows".to_string()])
+            .collect();
+        let lines = format_link_refs(&urls, 30).concat();
+        assert_eq!(lines[0], " [1] http://e.com/1");
+        assert_eq!(lines[9], "[10] http://e.com/10");
+        assert_eq!(lines[10], "[11] http://example.com/a/very");
+        assert_eq!(lines[11], "     /long/path/that/overflows");
+        assert!(lines.iter().all(|l| l.chars().count() <= 30), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_theme_colors_headings_and_code() {
+        let options = RenderOptions {
+            theme: Theme::named("dark256").unwrap(),
+            ..opts(true, false)
+        };
+        let lines = render(parser::parse("## Sub\n\nUse `x`."), 80, &options);
+        let joined = lines.join("\n");
+        assert!(joined.contains(&style::combine(&[style::BOLD, &style::fg_256(117)])), "Got: {:?}", lines);
+        assert!(joined.contains(&style::bg_256(237)), "Got: {:?}", lines);
+        assert!(!joined.contains(style::FG_BRIGHT_CYAN), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_ordered_style_labels() {
+        assert_eq!(OrderedStyle::Decimal.label(4), "4");
+        assert_eq!(OrderedStyle::Alpha.label(1), "a");
+        assert_eq!(OrderedStyle::Alpha.label(26), "z");
+        assert_eq!(OrderedStyle::Alpha.label(27), "aa");
+        assert_eq!(OrderedStyle::Roman.label(4), "iv");
+        assert_eq!(OrderedStyle::Roman.label(1994), "mcmxciv");
+        assert_eq!(OrderedStyle::Roman.label(0), "0");
+    }
+
+    #[test]
+    fn test_ordered_style_in_list() {
+        let options = RenderOptions {
+            ordered_style: OrderedStyle::Roman,
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse("3. three\n4. four"), 80, &options);
+        assert!(lines.iter().any(|l| l.trim() == "iii. three"), "Got: {:?}", lines);
+        assert!(lines.iter().any(|l| l.trim() == "iv. four"), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_overflowing_lines_finds_unbreakable_token() {
+        let input = format!("Short line.\n\n{}", "x".repeat(200));
+        let lines = render(parser::parse(&input), 80, &opts(true, false));
+        let over = overflowing_lines(&lines, 80);
+        assert_eq!(over.len(), 1, "Got: {:?}", over);
+        assert_eq!(over[0].1, 200);
+        assert!(overflowing_lines(&render(parser::parse("Short line."), 80, &opts(true, false)), 80).is_empty());
+    }
+
+    #[test]
+    fn test_table_caption_centered_above_table() {
+        let options = RenderOptions {
+            table_captions: true,
+            ..opts(false, false)
+        };
+        let input = "**Table 1: Sizes**\n\n| Name | Size in bytes |\n|------|------|\n| a | 1 |";
+        let lines = render(parser::parse(input), 80, &options);
+        let caption = lines.iter().position(|l| l.contains("Table 1: Sizes")).unwrap();
+        assert!(lines[caption + 1].trim_start().starts_with('┌'), "Got: {:?}", lines);
+        // Table is 4 + 13 + 7 = 24 wide, the caption 14: (24 - 14) / 2 = 5 after the margin
+        assert_eq!(lines[caption], format!("{}Table 1: Sizes", " ".repeat(7)));
+        assert_eq!(lines.iter().filter(|l| l.contains("Table 1")).count(), 1);
+
+        // Off by default, and only for a paragraph directly before the table
+        let lines = render(parser::parse(input), 80, &opts(false, false));
+        assert_eq!(lines.iter().find(|l| l.contains("Table 1")).unwrap(), "Table 1: Sizes");
+        let spaced = input.replace("\n\n|", "\n\n---\n\n|");
+        let lines = render(parser::parse(&spaced), 80, &options);
+        assert_eq!(lines.iter().find(|l| l.contains("Table 1")).unwrap(), "Table 1: Sizes");
+    }
+
+    #[test]
+    fn test_trim_trailing_keeps_resets() {
+        assert_eq!(trim_trailing("text   "), "text");
+        assert_eq!(trim_trailing("  indented"), "  indented");
+        let styled = format!("{}cell   {}  ", style::BOLD, style::RESET);
+        assert_eq!(trim_trailing(&styled), format!("{}cell{}", style::BOLD, style::RESET));
+        assert_eq!(trim_trailing("   "), "");
+    }
+
+    #[test]
+    fn test_show_comments() {
+        let input = "Text <!-- inline\nnote --> more.\n\n<!-- TODO: block\n  second line -->\n\nafter";
+        let hidden = render(parser::parse(input), 80, &opts(true, false)).join("\n");
+        assert!(!hidden.contains("note") && !hidden.contains("TODO"), "Got: {:?}", hidden);
+
+        let options = RenderOptions {
+            show_comments: true,
+            ..opts(true, false)
+        };
+        let lines = render(parser::parse(input), 80, &options);
+        let dim = style::combine(&[style::DIM, style::ITALIC]);
+        let inline = lines.iter().find(|l| l.contains("inline")).unwrap();
+        assert!(inline.contains(&format!("{}inline", dim)), "Got: {:?}", inline);
+        assert!(inline.contains("more."), "Got: {:?}", inline);
+        let todo = lines.iter().find(|l| l.contains("TODO")).unwrap();
+        assert!(todo.starts_with(&format!("{}TODO: block{}", dim, style::RESET)), "Got: {:?}", todo);
+        let second = lines.iter().find(|l| l.contains("second line")).unwrap();
+        assert!(second.starts_with(&dim), "Got: {:?}", second);
+
+        let options = RenderOptions {
+            show_comments: true,
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse(input), 80, &options);
+        assert!(lines.iter().any(|l| l == "Text <!-- inline note --> more."), "Got: {:?}", lines);
+        assert!(lines.iter().any(|l| l == "<!-- TODO: block"), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_ascii_output() {
+        let input = "# Title\n\n- item — “quoted” é\n- [x] done\n\n```\ncode\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n---";
+        let options = RenderOptions {
+            ascii: true,
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse(input), 40, &options);
+        assert!(lines.iter().all(|l| l.is_ascii()), "Got: {:?}", lines);
+        assert!(lines.iter().any(|l| l.trim() == "* item -- \"quoted\" ?"), "Got: {:?}", lines);
+        assert!(lines.iter().any(|l| l.trim_start().starts_with("+---")), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_image_placeholder() {
+        let lines = render(parser::parse("See ![a cat](cat.png) here."), 80, &opts(false, false));
+        assert!(lines.iter().any(|l| l == "See [image: a cat] here."), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_emphasis_persists_across_soft_break() {
+        // Each chunk carries its own style, so the text after the break is
+        // still italic even when it wraps onto a line of its own
+        for width in [80, 12] {
+            let lines = render(parser::parse("*italic\ntext* plain"), width, &opts(true, false));
+            let joined = lines.join("\n");
+            assert!(joined.contains(&format!("{}italic{}", style::ITALIC, style::RESET)), "Got: {:?}", lines);
+            assert!(joined.contains(&format!("{}text{}", style::ITALIC, style::RESET)), "Got: {:?}", lines);
+            assert!(!joined.contains(&format!("{}plain", style::ITALIC)), "Got: {:?}", lines);
+        }
+
+        let options = RenderOptions {
+            keep_markers: true,
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse("*italic\ntext*"), 80, &options);
+        assert!(lines.iter().any(|l| l == "_italic text_"), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_document_stats_counts_prose_only() {
+        let input = "# Title\n\nOne two three, see https://example.com or [the docs](http://x.io).\n\n\
+                     ## Code\n\nRun `cargo build` now.\n\n```\nlet ignored = words;\n```\n\n- four - five";
+        let stats = document_stats(&parser::parse(input));
+        // Title, One, two, three, see, or, the, docs, Code, Run, now, four, five
+        assert_eq!(stats.words, 13);
+        assert_eq!(stats.headings, 2);
+        assert_eq!(stats.reading_minutes(), 1);
+        assert_eq!(DocumentStats { words: 401, headings: 0 }.reading_minutes(), 3);
+
+        let options = RenderOptions {
+            stats: true,
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse(input), 40, &options);
+        assert_eq!(lines.last().unwrap(), "13 words, 1 min read, 2 headings");
+        assert!(lines[lines.len() - 2].starts_with("───"), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_table_header_repeat() {
+        let mut input = String::from("| Name | Value |\n|---|---|\n");
+        for i in 0..30 {
+            input.push_str(&format!("| row{} | {} |\n", i, i));
+        }
+        let options = RenderOptions {
+            table_header_repeat: 10,
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse(&input), 80, &options);
+        let headers: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].contains("Name")).collect();
+        assert_eq!(headers.len(), 3, "Got: {:?}", lines);
+        // Each repeat directly follows the tenth data row and has its separator
+        assert!(lines[headers[1] - 1].contains("row9 "), "Got: {:?}", lines);
+        assert!(lines[headers[1] + 1].trim_start().starts_with('├'), "Got: {:?}", lines);
+        assert!(lines[headers[2] - 1].contains("row19 "), "Got: {:?}", lines);
+
+        let lines = render(parser::parse(&input), 80, &opts(false, false));
+        assert_eq!(lines.iter().filter(|l| l.contains("Name")).count(), 1);
+    }
+
+    #[test]
+    fn test_code_block_source_is_kept_verbatim() {
+        let code = "fn main() {\n\tprintln!(\"tab\");\n\n    let x = 1; // spaces\n}\n";
+        let input = format!("Intro\n\n```rust\n{}```\n\n    indented\n\n```raw\nas is\n```", code);
+        let doc = render_document(parser::parse(&input), 40, &opts(true, false));
+        assert_eq!(doc.code_blocks.len(), 3);
+        assert_eq!(doc.code_blocks[0].lang, "rust");
+        assert_eq!(doc.code_blocks[0].source, code);
+        assert_eq!(doc.code_blocks[1].source, "indented\n");
+        assert_eq!(doc.code_blocks[2].source, "as is\n");
+        // The recorded range spans the block's borders
+        let range = doc.code_blocks[0].lines.clone();
+        assert!(doc.lines[range.start].contains("╭─ rust"));
+        assert!(doc.lines[range.end - 1].contains("╰───"));
+    }
+
+    #[test]
+    fn test_lead_style_marks_first_paragraph_after_title() {
+        let input = "Preface.\n\n# Title\n\n> quoted\n\nThe lead.\n\nBody text.";
+        let options = RenderOptions {
+            lead_style: true,
+            ..opts(true, false)
+        };
+        let lines = render(parser::parse(input), 80, &options);
+        let find = |text: &str| lines.iter().find(|l| l.contains(text)).unwrap().clone();
+        assert!(find("The lead.").contains(&format!("{}The lead.", style::ITALIC)), "Got: {:?}", lines);
+        assert!(!find("Body text.").contains(style::ITALIC), "Got: {:?}", lines);
+        assert!(!find("Preface.").contains(style::ITALIC), "Got: {:?}", lines);
+        assert!(!find("quoted").contains(style::ITALIC), "Got: {:?}", lines);
+
+        let lines = render(parser::parse(input), 80, &opts(true, false));
+        assert!(!lines.iter().any(|l| l.contains(style::ITALIC)), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_combining_marks_in_wrapping_and_truncation() {
+        let word = "re\u{301}sume\u{301}"; // "résumé", 6 columns
+        let input = [word; 6].join(" ");
+        let lines = render(parser::parse(&input), 22, &opts(false, false));
+        // Two words per 20-column line: 6 + 1 + 6 + 1 + 6 = 20
+        assert_eq!(lines.iter().filter(|l| !l.is_empty()).count(), 2, "Got: {:?}", lines);
+        assert!(lines.iter().all(|l| style::visible_len(l) <= 20), "Got: {:?}", lines);
+
+        // A mark right after the cut stays with its base character
+        assert_eq!(truncate_styled(word, 3, &Glyphs::UNICODE, false), "re\u{301}…");
+        assert_eq!(styled_tail(word, 5).1, "e\u{301}sume\u{301}");
+
+        let folded = fold(vec!["abcde\u{301}fgh".to_string()], 5);
+        assert_eq!(folded, vec!["abcde\u{301}".to_string(), "fgh".to_string()]);
+    }
+
+    #[test]
+    fn test_ansi_fence_keeps_color_codes() {
+        let input = "```ansi\n\x1b[31merror\x1b[0m: failed\n```\n\n```\n\x1b[31mliteral\n```";
+        let lines = render(parser::parse(input), 80, &opts(true, false));
+        let colored = lines.iter().find(|l| l.contains("error")).unwrap();
+        assert!(colored.contains("\x1b[31merror\x1b[0m: failed"), "Got: {:?}", colored);
+        assert_eq!(style::visible_len(colored), "  │ error: failed".chars().count());
+        // Escapes in ordinary code blocks are displayed, not interpreted
+        let literal = lines.iter().find(|l| l.contains("literal")).unwrap();
+        assert!(literal.contains("^[[31mliteral") && !literal.contains("\x1b[31m"), "Got: {:?}", literal);
+
+        let lines = render(parser::parse(input), 80, &opts(false, false));
+        assert!(lines.iter().any(|l| l == "  | error: failed"), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_ansi_fence_shows_non_color_escapes() {
+        let input = "```ansi\n\x1b]52;c;aGk=\x07\x1b[2J\x1b[H\x1b[1;32mok\x1b[0m\n```";
+        let lines = render(parser::parse(input), 80, &opts(true, false));
+        let line = lines.iter().find(|l| l.contains("ok")).unwrap();
+        assert!(line.contains("^[]52;c;aGk=^G^[[2J^[[H\x1b[1;32mok\x1b[0m"), "Got: {:?}", line);
+        assert!(!line.contains("\x1b]") && !line.contains("\x1b[2J") && !line.contains("\x1b[H"));
+
+        let lines = render(parser::parse(input), 80, &opts(false, false));
+        assert!(lines.iter().any(|l| l == "  | ^[]52;c;aGk=^G^[[2J^[[Hok"), "Got: {:?}", lines);
+    }
+
+    #[test]
+    fn test_visitor_collects_link_urls() {
+        struct Links(Vec<String>);
+        impl Visitor for Links {
+            fn visit(&mut self, event: &Event<'_>, _: &mut Renderer) -> bool {
+                if let Event::Start(Tag::Link { dest_url, .. }) = event {
+                    self.0.push(dest_url.to_string());
+                }
+                true
+            }
+        }
+        let input = "See [one](https://one.example) and <https://two.example>.\n\n\
+                     - [three](/three)\n\n| [four](four.md) |\n|---|\n| x |";
+        let mut links = Links(Vec::new());
+        let doc = render_with(parser::parse(input), 80, &opts(false, false), &mut links);
+        assert_eq!(
+            links.0,
+            ["https://one.example", "https://two.example", "/three", "four.md"]
+        );
+        // Rendering itself is unchanged
+        assert_eq!(doc.lines, render(parser::parse(input), 80, &opts(false, false)));
+    }
+
+    #[test]
+    fn test_visitor_replaces_code_blocks() {
+        struct Collapse(bool);
+        impl Visitor for Collapse {
+            fn visit(&mut self, event: &Event<'_>, renderer: &mut Renderer) -> bool {
+                match event {
+                    Event::Start(Tag::CodeBlock(_)) => {
+                        renderer.push_line("  [code omitted]");
+                        self.0 = true;
+                    }
+                    Event::End(TagEnd::CodeBlock) => self.0 = false,
+                    _ => return !self.0,
+                }
+                false
+            }
+        }
+        let input = "Before\n\n```rust\nfn main() {}\n```\n\nAfter";
+        let doc = render_with(parser::parse(input), 80, &opts(false, false), &mut Collapse(false));
+        assert!(doc.lines.iter().any(|l| l == "  [code omitted]"), "Got: {:?}", doc.lines);
+        assert!(!doc.lines.iter().any(|l| l.contains("fn main")));
+        assert!(doc.lines.iter().any(|l| l.contains("After")));
+    }
+
+    #[test]
+    fn test_nested_strong_keeps_outer_bold() {
+        let lines = render(parser::parse("**a **b** c**"), 80, &opts(true, false));
+        let text = lines.iter().find(|l| l.contains('c')).unwrap();
+        let tail = &text[text.rfind('b').unwrap()..];
+        // After the inner strong closes, " c" is still drawn bold
+        let c = tail.find(" c").unwrap();
+        assert!(tail[..c].contains(style::BOLD), "Got: {:?}", text);
+        assert!(!tail[..c].ends_with(style::RESET), "Got: {:?}", text);
+    }
+
+    #[test]
+    fn test_wide_bullet_continuation_indent() {
+        let opts = RenderOptions {
+            bullets: vec!["🍎".to_string()],
+            ..opts(false, false)
+        };
+        let input = "- one two three four five six seven eight\n  - nested";
+        let lines = render(parser::parse(input), 24, &opts);
+        // "  🍎 " is five columns wide: wrapped text and the nested list start there
+        assert_eq!(lines[0], "  🍎 one two three");
+        assert_eq!(lines[1], "     four five six");
+        assert_eq!(lines[2], "     seven eight");
+        assert_eq!(lines[3], "     🍎 nested");
+    }
+
+    #[test]
+    fn test_ascii_glyphs_match_transliteration() {
+        let (u, a) = (Glyphs::UNICODE, Glyphs::ASCII);
+        let mut pairs = vec![
+            (u.deep_bullet, a.deep_bullet),
+            (u.rule, a.rule),
+            (u.double_rule, a.double_rule),
+            (u.bar, a.bar),
+            (u.code_top, a.code_top),
+            (u.code_bottom, a.code_bottom),
+            (u.check, a.check),
+            (u.ellipsis, a.ellipsis),
+        ];
+        pairs.extend(u.bullets.into_iter().zip(a.bullets));
+        pairs.extend(u.table.into_iter().flatten().zip(a.table.into_iter().flatten()));
+        for (unicode, ascii) in pairs {
+            assert!(ascii.is_ascii());
+            // Lines converted after rendering, like the --head marker, agree
+            assert_eq!(to_ascii(unicode), ascii);
+        }
+    }
+
+    #[test]
+    fn test_ascii_replaces_every_decorative_glyph() {
+        let input = "# T\n\n> quote\n\n- a\n  - b\n    - c\n- [x] done\n\n```rust\nfn x() {}\n\n```\n\n\
+                     | a | b |\n|---|---|\n| 1 | 2 |\n\n---\n\nA very long line that gets cut off";
+        for use_color in [true, false] {
+            let options = RenderOptions {
+                ascii: true,
+                stats: true,
+                ..opts(use_color, false)
+            };
+            let lines = render(parser::parse(input), 30, &options);
+            assert!(lines.iter().all(|l| l.is_ascii()), "Got: {:?}", lines);
+            let options = RenderOptions { no_wrap: true, ..options };
+            let lines = render(parser::parse(input), 30, &options);
+            assert!(lines.iter().all(|l| l.is_ascii()), "Got: {:?}", lines);
+            // The ASCII ellipsis is budgeted at its own width
+            let cut = lines.iter().find(|l| l.contains("A very")).unwrap();
+            assert!(cut.ends_with(&format!("...{}", if use_color { style::RESET } else { "" })), "Got: {:?}", cut);
+            assert_eq!(style::visible_len(cut), 28, "Got: {:?}", cut);
+        }
+        // Bullets come from the ASCII glyphs, not transliterated Unicode
+        let options = RenderOptions { ascii: true, bullets: vec!["•".to_string()], ..opts(false, false) };
+        assert_eq!(RenderState::new(30, &options).bullets, ["•"]);
+        let options = RenderOptions { ascii: true, ..opts(false, false) };
+        assert_eq!(RenderState::new(30, &options).bullets, Glyphs::ASCII.bullets);
+    }
+
+    #[test]
+    fn test_underline_heading_style() {
+        let options = RenderOptions {
+            heading_style: HeadingStyle::Underline,
+            ..opts(false, false)
+        };
+        let lines = render(parser::parse("# Title\n\n## Part\n\n### Minor\n\n> # Quoted"), 30, &options);
+        let at = |text: &str| lines.iter().position(|l| l.contains(text)).unwrap();
+        // Full content width: 30 columns less the 2-column margin
+        assert_eq!(lines[at("Title") + 1], "═".repeat(28));
+        assert_eq!(lines[at("Part") + 1], "─".repeat(28));
+        assert!(lines[at("Minor") + 1].is_empty(), "Got: {:?}", lines);
+        assert_eq!(lines[at("Quoted") + 1], format!("  | {}", "═".repeat(24)));
+    }
+
+    #[test]
+    fn test_styled_lines_end_with_reset() {
+        let input = "# Title with `code`\n\n> quoted *text that wraps onto more lines* here\n\n\
+                     - item **bold**\n- [x] done\n\n```rust\nlet x = 1; plain\n```\n\n\
+                     ```ansi\n\x1b[31mred\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n[link](http://x.y) end";
+        for options in [opts(true, false), opts(true, true)] {
+            let lines = render(parser::parse(input), 24, &options);
+            for line in lines.iter().filter(|l| l.contains('\x1b')) {
+                assert!(line.ends_with(style::RESET), "Got: {:?}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_two_column_layout() {
+        let input: Vec<String> = (1..=20).map(|i| format!("Paragraph {}.", i)).collect();
+        let width = column_width(40, 2);
+        assert_eq!(width, 19);
+        let doc = render_document(parser::parse(&input.join("\n\n")), width, &opts(false, false));
+        assert_eq!(doc.lines.len(), 39);
+        let laid_out = columns(doc, 2, width as usize, &Glyphs::UNICODE);
+        assert_eq!(laid_out.lines.len(), 20);
+        // "Paragraph 11." opens the second column, after the padded first column and gutter
+        let row = laid_out.lines.iter().find(|l| l.contains("Paragraph 11.")).unwrap();
+        let second = row.find("Paragraph 11.").unwrap();
+        assert!(row[..second].ends_with(&" ".repeat(COLUMN_GUTTER)), "Got: {:?}", row);
+        assert_eq!(second, 19 + COLUMN_GUTTER);
+        assert!(laid_out.lines.iter().all(|l| style::visible_len(l) <= 40));
+
+        // Code blocks move with their lines; one split between columns is dropped
+        let input = "```\nfirst\n```\n\nText.\n\n```\nsplit\n\n```\n\n```\nlast\n```";
+        let doc = render_document(parser::parse(input), width, &opts(false, false));
+        let laid_out = columns(doc.clone(), 2, width as usize, &Glyphs::UNICODE);
+        let rows = laid_out.lines.len();
+        assert_eq!(laid_out.code_blocks.len(), 2, "Got: {:?}", doc);
+        for block in &laid_out.code_blocks {
+            assert!(block.lines.end <= rows);
+            let row = &laid_out.lines[block.lines.start + 1];
+            assert!(row.contains(block.source.trim()), "Got: {:?} for {:?}", row, block);
+        }
+        assert_eq!(laid_out.code_blocks[0].source, "first\n");
+        let last = &laid_out.code_blocks[1];
+        assert_eq!(last.source, "last\n");
+        assert_eq!(last.lines.start, doc.code_blocks[2].lines.start - rows);
+    }
+
+    #[test]
+    fn test_task_summary_counts_checked_items() {
+        let options = RenderOptions {
+            task_summary: true,
+            ..opts(false, false)
+        };
+        let input = "- [x] one\n- [ ] two\n- [x] three\n  - [ ] nested\n- [x] five\n\nText.\n\n- plain list";
+        let lines = render(parser::parse(input), 80, &options);
+        let summaries: Vec<&String> = lines.iter().filter(|l| l.contains("tasks complete")).collect();
+        assert_eq!(summaries, ["  3/5 tasks complete (60%)"], "Got: {:?}", lines);
+        // Right after the list's last item
+        let at = lines.iter().position(|l| l.contains("tasks complete")).unwrap();
+        assert!(lines[at - 1].contains("five"));
+        // Off by default
+        let lines = render(parser::parse(input), 80, &opts(false, false));
+        assert!(!lines.iter().any(|l| l.contains("tasks complete")));
+    }
+
+    #[test]
+    fn test_inline_code_in_heading_drops_background() {
+        let theme = Theme::default();
+        let lines = render(parser::parse("## Use `cargo`\n\nRun `cargo` and **`x`**."), 80, &opts(true, false));
+        let heading = lines.iter().find(|l| l.contains("Use")).unwrap();
+        let paragraph = lines.iter().find(|l| l.contains("Run")).unwrap();
+        assert!(heading.contains(&format!("{}cargo{}", theme.code_fg, style::RESET)), "Got: {:?}", heading);
+        assert!(!heading.contains(&theme.code_bg), "Got: {:?}", heading);
+        assert!(heading.contains(&style::combine(&[style::BOLD, &theme.headings[1]])), "Got: {:?}", heading);
+        assert!(paragraph.contains(&format!("{}cargo{}", theme.code_bg, style::RESET)), "Got: {:?}", paragraph);
+        // Code inside bold stays bold
+        assert!(paragraph.contains(&format!("{}{}x", style::BOLD, theme.code_bg)), "Got: {:?}", paragraph);
+    }
+
+    #[test]
+    fn test_hanging_indent_paragraph() {
+        let options = RenderOptions {
+            hanging_indent: 3,
+            ..opts(false, false)
+        };
+        let input = "one two three four five six seven eight nine ten eleven twelve\n\n# Short heading";
+        let lines = render(parser::parse(input), 24, &options);
+        let first = lines.iter().position(|l| l.starts_with("one")).unwrap();
+        assert_eq!(lines[first], "one two three four");
+        assert_eq!(lines[first + 1], "   five six seven");
+        assert_eq!(lines[first + 2], "   eight nine ten");
+        assert!(lines.iter().any(|l| l == "Short heading"));
+    }
+
+    #[test]
+    fn test_hard_break_in_table_cell_is_kept() {
+        let mut events = parser::parse("| a | b |\n|---|---|\n| x | y |\n");
+        let at = events.iter().position(|e| *e == Event::Text("x".into())).unwrap();
+        events.insert(at + 1, Event::HardBreak);
+        events.insert(at + 2, Event::Text("z".into()));
+        let end = events.iter().position(|e| matches!(e, Event::End(TagEnd::Table))).unwrap();
+
+        let mut renderer = Renderer::new(40, &opts(false, false));
+        for event in events[..end].iter().cloned() {
+            renderer.event(event);
+        }
+        assert_eq!(renderer.state.table_rows[1], ["x\nz", "y"]);
+
+        let lines = render(events, 40, &opts(false, false));
+        assert!(lines.iter().any(|l| l.contains("x") && l.contains("y")), "{:?}", lines);
+        assert!(lines.iter().any(|l| l.contains("z") && !l.contains("y")), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_code_box_borders_span_widest_line() {
+        let lines = render(parser::parse("```rust\nfn main() {\n    run();\n}\n```"), 40, &opts(false, false));
+        let top = lines.iter().position(|l| l.contains('╭')).unwrap();
+        let bottom = lines.iter().position(|l| l.contains('╰')).unwrap();
+        let widest = lines[top + 1..bottom].iter().map(|l| style::visible_len(l)).max().unwrap();
+        assert_eq!(lines[top], format!("  ╭─ rust {}", "─".repeat(widest - 10)));
+        assert_eq!(style::visible_len(&lines[top]), widest);
+        assert_eq!(style::visible_len(&lines[bottom]), widest);
+
+        // Long lines cap the box at the content width, as wide as a rule
+        let long = format!("```\n{}\n```\n\n---", "x".repeat(60));
+        let lines = render(parser::parse(&long), 40, &opts(false, false));
+        let top = lines.iter().find(|l| l.contains('╭')).unwrap();
+        let rule = lines.iter().find(|l| l.starts_with('─')).unwrap();
+        assert_eq!(style::visible_len(top), style::visible_len(rule));
+    }
+
+    #[test]
+    fn test_no_code_omits_code_blocks() {
+        let input = "Before.\n\n```rust\nfn main() {}\n```\n\nAfter `inline`.\n\n![logo](logo.png)";
+        let options = RenderOptions { no_code: true, ..opts(false, false) };
+        let lines = render(parser::parse(input), 40, &options);
+        assert!(lines.iter().any(|l| l.contains("Before.")));
+        assert!(lines.iter().any(|l| l.contains("After `inline`.")), "{:?}", lines);
+        assert!(!lines.iter().any(|l| l.contains("fn main") || l.contains('╭')), "{:?}", lines);
+        assert!(lines.iter().any(|l| l.contains("logo")));
+
+        let options = RenderOptions { no_images: true, no_tables: true, ..opts(false, false) };
+        let lines = render(parser::parse(input), 40, &options);
+        assert!(lines.iter().any(|l| l.contains("fn main")));
+        assert!(!lines.iter().any(|l| l.contains("logo")), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_rtl_paragraph_is_right_aligned() {
+        let input = "שלום עולם, זהו מסמך בעברית עם כמה מילים נוספות.\n\nLeft to right.";
+        let lines = render(parser::parse(input), 30, &opts(false, false));
+        let rtl: Vec<&String> = lines.iter().filter(|l| is_rtl(l)).collect();
+        assert!(rtl.len() > 1, "{:?}", lines);
+        for line in rtl {
+            assert_eq!(style::visible_len(line), 28, "{:?}", line);
+            assert!(line.starts_with(" "), "{:?}", line);
+        }
+        assert!(lines.iter().any(|l| l == "Left to right."), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_glossary_aligns_definitions() {
+        let input = "- **API** — Application programming interface\n- **Terminal** — A text window\n";
+        let options = RenderOptions { glossary: true, ..opts(false, false) };
+        let lines = render(parser::parse(input), 60, &options);
+        let api = lines.iter().find(|l| l.contains("API")).unwrap();
+        let term = lines.iter().find(|l| l.contains("Terminal")).unwrap();
+        assert_eq!(api.find("Application"), term.find("A text"), "{:?}", lines);
+        assert!(!api.contains('—'), "{:?}", lines);
+
+        // Wrapped definitions continue in the definition column
+        let lines = render(parser::parse(input), 24, &options);
+        let api = lines.iter().find(|l| l.contains("API")).unwrap();
+        let start = style::visible_len(&api[..api.find("Application").unwrap()]);
+        let next = lines.iter().find(|l| l.contains("interface")).unwrap();
+        assert_eq!(next.len() - next.trim_start().len(), start, "{:?}", lines);
+
+        // Without the option, or for other lists, items render as usual
+        let lines = render(parser::parse(input), 60, &opts(false, false));
+        assert!(lines.iter().any(|l| l.contains("API — Application")), "{:?}", lines);
+        let lines = render(parser::parse("- **API** — x\n- plain\n"), 60, &options);
+        assert!(lines.iter().any(|l| l.contains("API — x")), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_consecutive_code_blocks_are_separated() {
+        let input = "```\na\n```\n```\nb\n```\n";
+        for block_spacing in [0, 1, 2] {
+            let options = RenderOptions { block_spacing, ..opts(false, false) };
+            let lines = render(parser::parse(input), 40, &options);
+            let bottom = lines.iter().position(|l| l.contains('╰')).unwrap();
+            let top = lines.iter().rposition(|l| l.contains('╭')).unwrap();
+            assert!(top > bottom + 1, "{:?}", lines);
+            assert!(lines[bottom + 1..top].iter().all(|l| l.is_empty()), "{:?}", lines);
+        }
+    }
+
+    #[test]
+    fn test_over_wide_table_is_reported() {
+        let input = "Text.\n\n| Name | Description |\n|---|---|\n| markterm | Renders markdown files in the terminal with a pager |\n";
+        let doc = render_document(parser::parse(input), 30, &opts(false, false));
+        assert_eq!(doc.squeezed_tables.len(), 1);
+        let fit = &doc.squeezed_tables[0];
+        assert!(fit.natural > 30 && fit.fitted <= 30, "{:?}", fit);
+        assert!(doc.lines[fit.line].contains('┌'), "{:?}", doc.lines);
+
+        let doc = render_document(parser::parse(input), 120, &opts(false, false));
+        assert!(doc.squeezed_tables.is_empty());
+
+        // No room for any content past the borders: still reported
+        let three = "| a | b | c |\n|---|---|---|\n| one | two | three |\n";
+        let doc = render_document(parser::parse(three), 14, &opts(false, false));
+        assert_eq!(doc.squeezed_tables.len(), 1, "{:?}", doc.lines);
+        // Columns already at their minimum width aren't called narrowed
+        let doc = render_document(parser::parse("| a |\n|---|\n| b |\n"), 9, &opts(false, false));
+        let fit = &doc.squeezed_tables[0];
+        assert!(fit.fitted >= fit.natural, "{:?}", fit);
+        assert_eq!(fit.describe(), format!("is {} columns wide and can't be narrowed", fit.natural));
+        let fit = TableFit { line: 0, natural: 60, fitted: 30 };
+        assert_eq!(fit.describe(), "is 60 columns wide, narrowed to 30 (50%)");
+    }
+
+    #[test]
+    fn test_item_opening_with_quote_keeps_marker() {
+        let input = "1. > quoted text\n2. Text\n   > quote below\n";
+        let lines = render(parser::parse(input), 40, &opts(false, false));
+        assert!(lines.contains(&"  1. | quoted text".to_string()), "{:?}", lines);
+        assert!(!lines.iter().any(|l| l.trim() == "1."), "{:?}", lines);
+        // The bar sits under the item text, where the marker's line put it
+        assert!(lines.contains(&"  2. Text".to_string()), "{:?}", lines);
+        assert!(lines.contains(&"     | quote below".to_string()), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_streaming_matches_collected_rendering() {
+        let input = "# Title\n\nSome *text* with `code` and a [link](https://example.com).\n\n\
+                     - one\n- [x] two\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n```rust\nfn main() {}\n```\n\n\
+                     > quote[^1]\n\n[^1]: Note.\n";
+        for options in [
+            opts(true, false),
+            opts(false, true),
+            RenderOptions { no_code: true, ..opts(true, false) },
+            RenderOptions { grep: Some("text".into()), ..opts(false, false) },
+        ] {
+            let collected = render_document(parser::parse(input), 50, &options);
+            let streamed = render_stream(parser::parse_iter(input), 50, &options);
+            assert_eq!(streamed.lines, collected.lines);
+            assert_eq!(streamed.links, collected.links);
+            assert_eq!(streamed.footnotes, collected.footnotes);
+        }
+    }
+
+    #[test]
+    fn test_colorblind_theme_avoids_red_and_green() {
+        let theme = Theme::named("colorblind").unwrap();
+        let options = RenderOptions { theme: theme.clone(), ..opts(true, false) };
+        let input = "- [x] done\n- [ ] todo\n\n```diff\n-old\n+new\n```\n";
+        let lines = render(parser::parse(input), 40, &options);
+        let done = lines.iter().find(|l| l.contains("done")).unwrap();
+        let todo = lines.iter().find(|l| l.contains("todo")).unwrap();
+        assert!(done.contains(&format!("{}[✓]", style::combine(&[&theme.done, style::BOLD]))), "{:?}", done);
+        assert!(todo.contains(&format!("{}[ ]", theme.todo)), "{:?}", todo);
+        assert_ne!(theme.done, theme.todo);
+        for line in &lines {
+            assert!(!line.contains(style::FG_GREEN) && !line.contains(style::FG_RED), "{:?}", line);
+        }
+        assert!(lines.iter().any(|l| l.contains(&format!("{}+new", theme.added))), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_display_math_fraction_spans_lines() {
+        let lines = render(parser::parse("$$\\frac{a}{b}$$\n"), 40, &opts(false, false));
+        let bar = lines.iter().position(|l| l.contains("───")).unwrap();
+        assert_eq!(lines[bar - 1].trim(), "a");
+        assert_eq!(lines[bar].trim(), "───");
+        assert_eq!(lines[bar + 1].trim(), "b");
+        // Centered, not at the left margin
+        assert!(lines[bar].starts_with(&" ".repeat(15)), "{:?}", lines[bar]);
+
+        let raw = render(parser::parse("$$\\sqrt[3]{x}$$\n"), 40, &opts(false, false));
+        assert!(raw.iter().any(|l| l.trim() == "$$\\sqrt[3]{x}$$"), "{:?}", raw);
+    }
+
+    #[test]
+    fn test_task_items_align_with_plain_items() {
+        let input = "- plain\n- [ ] todo\n- [x] done\n  - [ ] nested\n  - nested plain\n";
+        let lines = render(parser::parse(input), 40, &opts(false, false));
+        let column = |word: &str| {
+            let line = lines.iter().find(|l| l.contains(word)).unwrap();
+            style::visible_len(&line[..line.find(word).unwrap()])
+        };
+        assert_eq!(column("todo"), column("plain"));
+        assert_eq!(column("done"), column("plain"));
+        assert_eq!(column("nested"), column("nested plain"));
+        // The checkbox replaces the bullet rather than following it
+        assert!(!lines.iter().any(|l| l.contains("• [")), "{:?}", lines);
+        // Numbered items keep their numbers
+        let numbered = render(parser::parse("1. [ ] first\n"), 40, &opts(false, false));
+        assert!(numbered.iter().any(|l| l.contains("1. [ ] first")), "{:?}", numbered);
+
+        // In a loose list the item text is a paragraph, which starts on the marker's line
+        let loose = render(parser::parse("- plain item\n\n- [ ] task item\n"), 40, &opts(false, false));
+        let column = |word: &str| {
+            let line = loose.iter().find(|l| l.contains(word)).unwrap();
+            style::visible_len(&line[..line.find(word).unwrap()])
+        };
+        assert_eq!(column("task item"), column("plain item"), "{:?}", loose);
+        assert!(loose.iter().any(|l| l.ends_with("[ ] task item")), "{:?}", loose);
+        assert!(!loose.iter().any(|l| l.trim() == "•" || l.contains("• [")), "{:?}", loose);
+        // Loose items are still separated by a blank line
+        assert_eq!(loose[..3], ["  • plain item", "", "[ ] task item"], "{:?}", loose);
+    }
+
+    #[test]
+    fn test_code_link_text_is_underlined() {
+        let theme = Theme::default();
+        let lines = render(parser::parse("[`parse`](https://docs.rs)\n"), 40, &opts(true, false));
+        let line = lines.iter().find(|l| l.contains("parse")).unwrap();
+        let code = line.find(&theme.code_bg).expect("code background");
+        let underline = line.find(style::UNDERLINE).expect("link underline");
+        // Both styles are set before the code text starts
+        assert!(underline < line.find("parse").unwrap() && code < line.find("parse").unwrap());
+        // The URL after the text is not underlined
+        let url = line.find("(https://docs.rs)").unwrap();
+        assert!(line[..url].ends_with(&format!("{} {}", style::RESET, style::DIM)), "{:?}", line);
+    }
+
+    #[test]
+    fn test_first_line_is_never_blank() {
+        let inputs = [
+            "Para",
+            "# Heading",
+            "\n\n\n## After blank lines",
+            "<!-- hidden -->\n\nPara",
+            "<div>\nhtml\n</div>\n\nPara",
+            "---\n\nAfter a rule",
+            "> quote",
+            "- item\n- [ ] task",
+            "1. one",
+            "```rust\nfn main() {}\n```",
+            "```raw\nart\n```",
+            "    indented code",
+            "| a |\n|---|\n| b |",
+            "Caption\n| a |\n|---|\n| b |",
+            "![alt](x.png)",
+            "$$\\frac{a}{b}$$",
+            "[^1]: note\n\nText[^1]",
+            "Lead paragraph.\n\nSecond.",
+        ];
+        let variants = [
+            opts(false, false),
+            opts(true, true),
+            RenderOptions { block_spacing: 3, show_comments: true, ..opts(true, false) },
+            RenderOptions { table_captions: true, lead_style: true, stats: true, ..opts(false, false) },
+            RenderOptions { heading_style: HeadingStyle::Underline, task_summary: true, ..opts(true, false) },
+            RenderOptions { no_tables: true, no_code: true, no_images: true, ..opts(false, false) },
+        ];
+        for options in &variants {
+            for input in inputs {
+                let lines = render(parser::parse(input), 40, options);
+                if let Some(first) = lines.first() {
+                    assert!(!style::strip_ansi(first).trim().is_empty(), "{:?} -> {:?}", input, lines);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_reference_images_resolve_or_are_flagged() {
+        let input = "![Logo][logo] ![Chart][missing] [not][a-link]\n\n[logo]: logo.png\n";
+        let lines = render(parser::parse(input), 80, &opts(false, false));
+        assert_eq!(
+            lines[0],
+            "[image: Logo] [image: Chart (unresolved: missing)] [not][a-link]"
+        );
+        let colored = render(parser::parse(input), 80, &opts(true, false));
+        let flag = format!("{} (unresolved:", Theme::default().removed);
+        assert!(colored[0].contains(&flag), "{:?}", colored[0]);
+    }
+
+    #[test]
+    fn test_prose_width_leaves_code_wide() {
+        let prose = "word ".repeat(30);
+        let code = format!("let long_line = \"{}\";", "x".repeat(50));
+        let input = format!("{}\n\n```\n{}\n```\n\n{}\n", prose, code, prose);
+        let options = RenderOptions { prose_width: 40, ..opts(false, false) };
+        let lines = render(parser::parse(&input), 100, &options);
+        let widest_prose = lines
+            .iter()
+            .filter(|l| l.contains("word"))
+            .map(|l| style::visible_len(l))
+            .max()
+            .unwrap();
+        assert!(widest_prose <= 40 && widest_prose > 30, "{:?}", lines);
+        let code_line = lines.iter().find(|l| l.contains("long_line")).unwrap();
+        assert!(code_line.contains(&code), "{:?}", code_line);
+        assert!(style::visible_len(code_line) > 70);
+    }
+
+    #[test]
+    fn test_emphasis_inside_inline_code_is_literal() {
+        let input = "Say `**not bold**` here\n";
+        let colored = render(parser::parse(input), 40, &opts(true, false));
+        let line = colored.iter().find(|l| l.contains("not bold")).unwrap();
+        // The background starts before the asterisks and ends after them
+        let open = format!("{}**not", Theme::default().code_bg);
+        let start = line.find(&open).expect("background before the asterisks");
+        let close = line[start..].find(style::RESET).unwrap() + start;
+        assert!(line[start..close].ends_with("bold**"), "{:?}", line);
+        assert!(!line.contains(style::BOLD), "{:?}", line);
+
+        let plain = render(parser::parse(input), 40, &opts(false, false));
+        assert!(plain.iter().any(|l| l.trim() == "Say `**not bold**` here"), "{:?}", plain);
+    }
+
+    #[test]
+    fn test_inline_code_brackets_style() {
+        let input = "Run `make` now\n";
+        for (ascii, expected) in [(false, "Run ‹make› now"), (true, "Run [make] now")] {
+            let options = RenderOptions {
+                inline_code_style: InlineCodeStyle::Brackets,
+                ascii,
+                ..opts(true, false)
+            };
+            let lines = render(parser::parse(input), 40, &options);
+            let line = lines.iter().find(|l| l.contains("make")).unwrap();
+            assert_eq!(style::strip_ansi(line).trim(), expected);
+            assert!(!line.contains(&Theme::default().code_bg), "{:?}", line);
+            assert!(!line.contains("\x1b[48"), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_legal_numbering_prefixes_parents() {
+        let input = "1. One\n   1. First\n   2. Second\n      - bullet\n        1. Deep\n2. Two\n   1. Again\n";
+        let options = RenderOptions { ordered_style: OrderedStyle::Legal, ..opts(false, false) };
+        let lines = render(parser::parse(input), 40, &options);
+        let marker = |text: &str| {
+            let line = lines.iter().find(|l| l.ends_with(text)).unwrap();
+            line.trim_start().strip_suffix(text).unwrap().trim_end().to_string()
+        };
+        assert_eq!(marker("One"), "1.");
+        assert_eq!(marker("First"), "1.1.");
+        assert_eq!(marker("Second"), "1.2.");
+        // The bulleted level in between adds nothing to the number
+        assert_eq!(marker("Deep"), "1.2.1.");
+        assert_eq!(marker("Two"), "2.");
+        assert_eq!(marker("Again"), "2.1.");
+    }
+
+    #[test]
+    fn test_max_paragraph_lines_clamps_with_ellipsis() {
+        let input = "one two three four five six seven eight nine ten eleven twelve thirteen fourteen\n\nShort.";
+        let options = RenderOptions { max_paragraph_lines: 2, ..opts(false, false) };
+        let lines = render(parser::parse(input), 22, &options);
+        let first: Vec<&String> = lines.iter().take_while(|l| !l.is_empty()).collect();
+        assert_eq!(first.len(), 2, "Got: {:?}", lines);
+        assert!(first[1].ends_with('…'), "Got: {:?}", lines);
+        assert!(first.iter().all(|l| style::visible_len(l) <= 20), "Got: {:?}", lines);
+        // Paragraphs within the cap are untouched
+        assert_eq!(lines.last().unwrap(), "Short.");
+
+        // A hard break flushes the paragraph in parts; the ellipsis is added once
+        let lines = render(parser::parse("a  \nb  \nc  \nd"), 22, &options);
+        assert_eq!(lines, ["a", "b…"]);
+    }
 }
@@ -1,4 +1,4 @@
-/// ANSI/VT100 escape code helpers for terminal styling.
+//! ANSI/VT100 escape code helpers for terminal styling.
 
 pub const RESET: &str = "\x1b[0m";
 pub const BOLD: &str = "\x1b[1m";
@@ -7,6 +7,7 @@ pub const ITALIC: &str = "\x1b[3m";
 pub const UNDERLINE: &str = "\x1b[4m";
 pub const REVERSE: &str = "\x1b[7m";
 pub const STRIKETHROUGH: &str = "\x1b[9m";
+pub const NO_REVERSE: &str = "\x1b[27m";
 
 // Foreground colors
 pub const FG_RED: &str = "\x1b[31m";
@@ -24,9 +25,133 @@ pub const FG_BRIGHT_GREEN: &str = "\x1b[92m";
 // Background colors
 pub const BG_GREY: &str = "\x1b[48;5;236m";
 
-/// Build a style string from multiple codes.
+/// Foreground color `n` from the 256-color palette.
+pub fn fg_256(n: u8) -> String {
+    format!("\x1b[38;5;{}m", n)
+}
+
+/// Background color `n` from the 256-color palette.
+pub fn bg_256(n: u8) -> String {
+    format!("\x1b[48;5;{}m", n)
+}
+
+/// Colors for the themeable parts of the output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// Heading colors for levels 1, 2 and 3.
+    pub headings: [String; 3],
+    /// List markers and footnote references.
+    pub accent: String,
+    /// Inline code background.
+    pub code_bg: String,
+    /// Inline code color in headings, where a background would clash.
+    pub code_fg: String,
+    /// Checked task list boxes.
+    pub done: String,
+    /// Unchecked task list boxes.
+    pub todo: String,
+    /// Added and removed lines in `diff` code blocks.
+    pub added: String,
+    pub removed: String,
+    /// Blockquote bar colors, cycled by nesting level.
+    pub quote_bars: Vec<String>,
+    /// The pager's `~` lines past the end of the document.
+    pub filler: String,
+}
+
+impl Default for Theme {
+    /// The 16-color theme used by `auto`, `dark` and `light`.
+    fn default() -> Self {
+        Theme {
+            headings: [FG_BRIGHT_WHITE.into(), FG_BRIGHT_CYAN.into(), FG_BRIGHT_YELLOW.into()],
+            accent: FG_CYAN.into(),
+            code_bg: BG_GREY.into(),
+            code_fg: FG_MAGENTA.into(),
+            done: FG_GREEN.into(),
+            todo: DIM.into(),
+            added: FG_GREEN.into(),
+            removed: FG_RED.into(),
+            quote_bars: [DIM, FG_CYAN, FG_MAGENTA, FG_GREEN].map(String::from).to_vec(),
+            filler: DIM.into(),
+        }
+    }
+}
+
+impl Theme {
+    /// Theme names accepted by `--theme` (`none` disables color entirely).
+    pub const NAMES: &'static [&'static str] =
+        &["auto", "dark", "light", "dark256", "light256", "colorblind", "none"];
+
+    /// Look up a theme by name; `none` and unknown names have no theme.
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "auto" | "dark" | "light" => Some(Theme::default()),
+            "dark256" => Some(Theme {
+                headings: [fg_256(231), fg_256(117), fg_256(222)],
+                accent: fg_256(75),
+                code_bg: bg_256(237),
+                code_fg: fg_256(180),
+                done: fg_256(114),
+                todo: DIM.into(),
+                added: FG_GREEN.into(),
+                removed: FG_RED.into(),
+                quote_bars: [244, 75, 176, 114].map(fg_256).to_vec(),
+                filler: fg_256(240),
+            }),
+            "light256" => Some(Theme {
+                headings: [fg_256(16), fg_256(25), fg_256(130)],
+                accent: fg_256(31),
+                code_bg: bg_256(254),
+                code_fg: fg_256(96),
+                done: fg_256(28),
+                todo: DIM.into(),
+                added: FG_GREEN.into(),
+                removed: FG_RED.into(),
+                quote_bars: [246, 31, 127, 28].map(fg_256).to_vec(),
+                filler: fg_256(250),
+            }),
+            // Blue and orange instead of green and red, which look alike
+            // with red-green color blindness
+            "colorblind" => Some(Theme {
+                done: fg_256(33),
+                todo: fg_256(208),
+                added: fg_256(33),
+                removed: fg_256(208),
+                quote_bars: [DIM, FG_CYAN, FG_MAGENTA, FG_BLUE].map(String::from).to_vec(),
+                ..Theme::default()
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Build a style string from multiple codes, merging consecutive SGR codes
+/// into one sequence: `BOLD` and `ITALIC` become `\x1b[1;3m`. Empty codes are
+/// skipped and anything else is passed through as is.
 pub fn combine(codes: &[&str]) -> String {
-    codes.concat()
+    let mut out = String::new();
+    let mut params: Vec<&str> = Vec::new();
+    let flush = |out: &mut String, params: &mut Vec<&str>| {
+        if !params.is_empty() {
+            out.push_str(&format!("\x1b[{}m", params.join(";")));
+            params.clear();
+        }
+    };
+    for code in codes.iter().filter(|c| !c.is_empty()) {
+        let sgr = code
+            .strip_prefix("\x1b[")
+            .and_then(|c| c.strip_suffix('m'))
+            .filter(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit() || b == b';'));
+        match sgr {
+            Some(p) => params.push(p),
+            None => {
+                flush(&mut out, &mut params);
+                out.push_str(code);
+            }
+        }
+    }
+    flush(&mut out, &mut params);
+    out
 }
 
 /// Wrap text with a style, appending RESET at the end.
@@ -37,9 +162,86 @@ pub fn styled(text: &str, codes: &[&str], use_color: bool) -> String {
     format!("{}{}{}", combine(codes), text, RESET)
 }
 
+/// Display columns taken by `ch`: zero for combining marks and other
+/// zero-width characters, which draw over the preceding character, and two
+/// for wide East Asian characters and emoji.
+pub fn char_width(ch: char) -> usize {
+    match ch {
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{200B}'..='\u{200D}' => 0,
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{303E}'
+        | '\u{3041}'..='\u{33FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F680}'..='\u{1F6FF}'
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{20000}'..='\u{3FFFD}' => 2,
+        _ => 1,
+    }
+}
+
+/// Where a scan is relative to escape sequences.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum Escape {
+    #[default]
+    Text,
+    /// Just after ESC
+    Start,
+    /// A CSI or other sequence, which ends at its first letter
+    Csi,
+    /// An OSC sequence (`ESC ]`), which ends at BEL or `ESC \`
+    Osc,
+    /// ESC inside an OSC sequence, starting its `ESC \` terminator
+    OscEsc,
+}
+
+impl Escape {
+    /// Advance past `ch`, returning whether it belongs to an escape sequence.
+    fn step(&mut self, ch: char) -> bool {
+        *self = match (*self, ch) {
+            (Escape::Text, '\x1b') => Escape::Start,
+            (Escape::Text, _) => return false,
+            (Escape::Start, ']') => Escape::Osc,
+            (Escape::Start | Escape::Csi, c) if c.is_ascii_alphabetic() => Escape::Text,
+            (Escape::Start | Escape::Csi, _) => Escape::Csi,
+            (Escape::Osc, '\x07') | (Escape::OscEsc, _) => Escape::Text,
+            (Escape::Osc, '\x1b') => Escape::OscEsc,
+            (Escape::Osc, _) => Escape::Osc,
+        };
+        true
+    }
+}
+
 /// Calculate the display width of a string, ignoring ANSI escape sequences.
 pub fn visible_len(s: &str) -> usize {
-    let mut len = 0;
+    let mut escape = Escape::default();
+    s.chars().filter(|&ch| !escape.step(ch)).map(char_width).sum()
+}
+
+/// Remove ANSI escape sequences, leaving only the visible text.
+pub fn strip_ansi(s: &str) -> String {
+    let mut escape = Escape::default();
+    s.chars().filter(|&ch| !escape.step(ch)).collect()
+}
+
+/// Insert `on` before visible column `start` and `off` before visible column `end`,
+/// skipping over existing escape sequences.
+pub fn insert_at_columns(s: &str, start: usize, end: usize, on: &str, off: &str) -> String {
+    let mut result = String::with_capacity(s.len() + on.len() + off.len());
+    let mut col = 0;
     let mut in_escape = false;
     for ch in s.chars() {
         if in_escape {
@@ -48,11 +250,26 @@ pub fn visible_len(s: &str) -> usize {
             }
         } else if ch == '\x1b' {
             in_escape = true;
+        } else if char_width(ch) == 0 {
+            // Stays attached to its base character
         } else {
-            len += 1;
+            if col == start {
+                result.push_str(on);
+            }
+            if col == end {
+                result.push_str(off);
+            }
+            col += char_width(ch);
         }
+        result.push(ch);
+    }
+    if col <= start {
+        result.push_str(on);
     }
-    len
+    if col <= end {
+        result.push_str(off);
+    }
+    result
 }
 
 #[cfg(test)]
@@ -70,6 +287,45 @@ mod tests {
         assert_eq!(visible_len(&s), 5);
     }
 
+    #[test]
+    fn test_256_color_codes() {
+        assert_eq!(fg_256(208), "\x1b[38;5;208m");
+        assert_eq!(bg_256(0), "\x1b[48;5;0m");
+        assert_eq!(visible_len(&format!("{}x{}", fg_256(255), RESET)), 1);
+    }
+
+    #[test]
+    fn test_named_themes() {
+        assert_eq!(Theme::named("dark"), Some(Theme::default()));
+        let dark = Theme::named("dark256").unwrap();
+        assert!(dark.headings[1].starts_with("\x1b[38;5;"));
+        assert_ne!(Theme::named("light256"), Some(dark));
+        assert!(Theme::named("none").is_none());
+    }
+
+    #[test]
+    fn test_combining_marks_have_zero_width() {
+        // "café" and "naïve" with decomposed accents
+        assert_eq!(visible_len("cafe\u{301}"), 4);
+        assert_eq!(visible_len(&format!("{}nai\u{308}ve{}", BOLD, RESET)), 5);
+        assert_eq!(
+            insert_at_columns("e\u{301}x", 1, 2, "[", "]"),
+            "e\u{301}[x]"
+        );
+    }
+
+    #[test]
+    fn test_wide_characters_take_two_columns() {
+        assert_eq!(visible_len("🍎 日本"), 7);
+        assert_eq!(visible_len("•◦▪"), 3);
+        assert_eq!(insert_at_columns("日本x", 4, 5, "[", "]"), "日本[x]");
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi(&format!("{}red{} plain", FG_RED, RESET)), "red plain");
+    }
+
     #[test]
     fn test_styled_no_color() {
         assert_eq!(styled("hi", &[BOLD], false), "hi");
@@ -82,4 +338,29 @@ mod tests {
         assert!(result.ends_with("\x1b[0m"));
         assert!(result.contains("hi"));
     }
+
+    #[test]
+    fn test_insert_at_columns_skips_escapes() {
+        let s = format!("ab{}cd{}ef", DIM, RESET);
+        let result = insert_at_columns(&s, 2, 4, "[", "]");
+        assert_eq!(result, format!("ab{}[cd{}]ef", DIM, RESET));
+    }
+
+    #[test]
+    fn test_combine_merges_sgr_codes() {
+        assert_eq!(combine(&[BOLD, ITALIC]), "\x1b[1;3m");
+        assert_eq!(combine(&[BOLD, &fg_256(208), UNDERLINE]), "\x1b[1;38;5;208;4m");
+        assert_eq!(combine(&[]), "");
+        // Empty and non-SGR codes don't break the merge
+        assert_eq!(combine(&[BOLD, "", ITALIC]), "\x1b[1;3m");
+        assert_eq!(combine(&[BOLD, "\x1b]8;;\x07", ITALIC]), "\x1b[1m\x1b]8;;\x07\x1b[3m");
+        assert_eq!(visible_len(&styled("hi", &[BOLD, ITALIC, FG_RED], true)), 2);
+    }
+
+    #[test]
+    fn test_visible_len_skips_osc_sequences() {
+        let link = "\x1b]8;;http://example.com\x07site\x1b]8;;\x07";
+        assert_eq!(visible_len(link), 4);
+        assert_eq!(strip_ansi("\x1b]0;title\x1b\\ok"), "ok");
+    }
 }
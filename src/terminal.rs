@@ -1,8 +1,196 @@
-/// Terminal capability detection and dimension queries.
+//! Terminal capability detection and dimension queries.
 
 use crossterm::terminal;
+use std::fmt;
+use std::str::FromStr;
+
+/// Size used when the terminal can't be queried (e.g. output is piped).
+pub const DEFAULT_SIZE: (u16, u16) = (80, 24);
 
 /// Returns (width, height) of the terminal, with sensible defaults.
 pub fn size() -> (u16, u16) {
-    terminal::size().unwrap_or((80, 24))
+    query_size().unwrap_or(DEFAULT_SIZE)
+}
+
+/// Returns (width, height) of the terminal, or `None` if it can't be queried.
+pub fn query_size() -> Option<(u16, u16)> {
+    terminal::size().ok()
+}
+
+/// Resolve the rendering width from, in order of precedence: the `--width`
+/// flag, the `MARKTERM_WIDTH` environment variable, then the terminal query.
+/// An unparseable env value is ignored with a warning on stderr.
+pub fn resolve_width(flag: Option<u16>, env: Option<&str>, term_width: u16) -> u16 {
+    resolve_width_source(flag, env, Some(term_width)).0
+}
+
+/// Where the rendering width came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WidthSource {
+    /// `--width` (or `--fold`)
+    Flag,
+    /// The `MARKTERM_WIDTH` environment variable
+    Env,
+    /// The terminal's reported size
+    Tty,
+    /// The terminal couldn't be queried
+    Default,
+}
+
+impl fmt::Display for WidthSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WidthSource::Flag => "flag",
+            WidthSource::Env => "env",
+            WidthSource::Tty => "tty",
+            WidthSource::Default => "default",
+        })
+    }
+}
+
+/// Like [`resolve_width`], but also reports which source won. `term_width`
+/// is `None` when the terminal size is unknown.
+pub fn resolve_width_source(
+    flag: Option<u16>,
+    env: Option<&str>,
+    term_width: Option<u16>,
+) -> (u16, WidthSource) {
+    if let Some(w) = flag {
+        return (w, WidthSource::Flag);
+    }
+    if let Some(value) = env {
+        match value.trim().parse::<u16>() {
+            Ok(w) => return (w, WidthSource::Env),
+            Err(_) => eprintln!("markterm: ignoring invalid MARKTERM_WIDTH value '{}'", value),
+        }
+    }
+    match term_width {
+        Some(w) => (w, WidthSource::Tty),
+        None => (DEFAULT_SIZE.0, WidthSource::Default),
+    }
+}
+
+/// Terminal graphics protocol used to draw inline images.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageProtocol {
+    Kitty,
+    Iterm,
+    Sixel,
+    /// No graphics: images render as a text placeholder
+    #[default]
+    None,
+}
+
+impl FromStr for ImageProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kitty" => Ok(ImageProtocol::Kitty),
+            "iterm" => Ok(ImageProtocol::Iterm),
+            "sixel" => Ok(ImageProtocol::Sixel),
+            "none" => Ok(ImageProtocol::None),
+            _ => Err(format!("unknown image protocol '{}' (expected kitty, iterm, sixel, none)", s)),
+        }
+    }
+}
+
+/// Make sure escape codes written to the terminal are interpreted, and say
+/// whether they will be. Windows consoles leave virtual terminal processing
+/// off until a program asks for it, and a legacy console (before Windows 10)
+/// can't turn it on at all. Other terminals always understand escape codes.
+pub fn enable_ansi() -> bool {
+    #[cfg(windows)]
+    {
+        crossterm::ansi_support::supports_ansi()
+    }
+    #[cfg(not(windows))]
+    {
+        true
+    }
+}
+
+/// Whether output has to go without escape codes: only a terminal that can't
+/// interpret them needs that, so `ansi` is not even asked for piped output.
+pub fn needs_plain_output(is_tty: bool, ansi: impl FnOnce() -> bool) -> bool {
+    is_tty && !ansi()
+}
+
+/// Guess the image protocol from the environment, looked up through `env`
+/// (`TERM`, `TERM_PROGRAM`, `LC_TERMINAL`, `KITTY_WINDOW_ID`). Unknown
+/// terminals get [`ImageProtocol::None`].
+pub fn detect_image_protocol(env: impl Fn(&str) -> Option<String>) -> ImageProtocol {
+    let term = env("TERM").unwrap_or_default();
+    let program = env("TERM_PROGRAM").unwrap_or_default();
+
+    if env("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || program == "ghostty" {
+        ImageProtocol::Kitty
+    } else if program == "iTerm.app"
+        || program == "WezTerm"
+        || env("LC_TERMINAL").is_some_and(|t| t == "iTerm2")
+    {
+        ImageProtocol::Iterm
+    } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+        ImageProtocol::Sixel
+    } else {
+        ImageProtocol::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_width_precedence() {
+        assert_eq!(resolve_width(Some(60), Some("100"), 80), 60);
+        assert_eq!(resolve_width(None, Some("100"), 80), 100);
+        assert_eq!(resolve_width(None, None, 80), 80);
+    }
+
+    fn detect(vars: &[(&str, &str)]) -> ImageProtocol {
+        detect_image_protocol(|name| {
+            vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+        })
+    }
+
+    #[test]
+    fn test_detect_image_protocol() {
+        assert_eq!(detect(&[("TERM", "xterm-kitty")]), ImageProtocol::Kitty);
+        assert_eq!(detect(&[("TERM", "xterm-256color"), ("KITTY_WINDOW_ID", "1")]), ImageProtocol::Kitty);
+        assert_eq!(detect(&[("TERM", "xterm-256color"), ("TERM_PROGRAM", "iTerm.app")]), ImageProtocol::Iterm);
+        assert_eq!(detect(&[("TERM", "screen"), ("LC_TERMINAL", "iTerm2")]), ImageProtocol::Iterm);
+        assert_eq!(detect(&[("TERM", "foot")]), ImageProtocol::Sixel);
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), ImageProtocol::None);
+        assert_eq!(detect(&[]), ImageProtocol::None);
+    }
+
+    #[test]
+    fn test_resolve_width_reports_source() {
+        assert_eq!(resolve_width_source(Some(60), None, Some(120)), (60, WidthSource::Flag));
+        assert_eq!(resolve_width_source(None, None, Some(120)), (120, WidthSource::Tty));
+        assert_eq!(resolve_width_source(None, Some("100"), Some(120)), (100, WidthSource::Env));
+        assert_eq!(resolve_width_source(None, None, None), (80, WidthSource::Default));
+        assert_eq!(WidthSource::Flag.to_string(), "flag");
+        assert_eq!(WidthSource::Tty.to_string(), "tty");
+    }
+
+    #[test]
+    fn test_resolve_width_ignores_invalid_env() {
+        assert_eq!(resolve_width(None, Some("wide"), 80), 80);
+        assert_eq!(resolve_width(None, Some("-5"), 80), 80);
+    }
+
+    #[test]
+    fn test_plain_output_only_for_terminals_without_ansi() {
+        assert!(!needs_plain_output(false, || panic!("not asked for piped output")));
+        assert!(!needs_plain_output(true, || true));
+        assert!(needs_plain_output(true, || false));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_ansi_always_enabled_off_windows() {
+        assert!(enable_ansi());
+    }
 }
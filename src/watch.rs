@@ -0,0 +1,79 @@
+//! Polling file watcher used by `--watch` to re-render on change.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long the file must stay unchanged before a change is reported.
+pub const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Detects modifications by polling the file's mtime. Bursts of writes (editors
+/// often save in several steps) are collapsed into a single change.
+pub struct FileWatcher {
+    path: PathBuf,
+    last_mtime: Option<SystemTime>,
+    pending_since: Option<Instant>,
+}
+
+impl FileWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let last_mtime = mtime(&path);
+        Self {
+            path,
+            last_mtime,
+            pending_since: None,
+        }
+    }
+
+    /// Returns true once per settled change to the file.
+    pub fn poll(&mut self) -> bool {
+        let current = mtime(&self.path);
+        self.observe(current, Instant::now())
+    }
+
+    fn observe(&mut self, mtime: Option<SystemTime>, now: Instant) -> bool {
+        if mtime != self.last_mtime {
+            self.last_mtime = mtime;
+            self.pending_since = Some(now);
+            return false;
+        }
+        match self.pending_since {
+            Some(since) if now.duration_since(since) >= DEBOUNCE => {
+                self.pending_since = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn mtime(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_reported_once_after_debounce() {
+        let mut watcher = FileWatcher {
+            path: PathBuf::new(),
+            last_mtime: None,
+            pending_since: None,
+        };
+        let start = Instant::now();
+        let t1 = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+        let t2 = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(2));
+
+        assert!(!watcher.observe(None, start));
+        assert!(!watcher.observe(t1, start));
+        // A second write within the debounce window restarts it
+        assert!(!watcher.observe(t2, start + Duration::from_millis(100)));
+        assert!(!watcher.observe(t2, start + Duration::from_millis(250)));
+        assert!(watcher.observe(t2, start + Duration::from_millis(300) + DEBOUNCE));
+        // Reported only once
+        assert!(!watcher.observe(t2, start + Duration::from_secs(5)));
+    }
+}
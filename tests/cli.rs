@@ -0,0 +1,167 @@
+//! End-to-end tests that run the markterm binary.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run_with_stdin(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_markterm"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn markterm");
+    // The process may exit before reading stdin (e.g. on a missing file)
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_empty_input_quiet_exits_2_silently() {
+    let output = run_with_stdin(&["--quiet", "-"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn test_empty_input_reports_message() {
+    let output = run_with_stdin(&["-"], "\n\n");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no renderable content"));
+}
+
+#[test]
+fn test_missing_file_exits_1() {
+    let output = run_with_stdin(&["/nonexistent/markterm-test.md"], "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_content_exits_0() {
+    let output = run_with_stdin(&["-"], "# Hello\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Hello"));
+}
+
+#[test]
+fn test_head_limits_output() {
+    let input: String = (0..40).map(|i| format!("Paragraph {}\n\n", i)).collect();
+    let output = run_with_stdin(&["--head", "5", "-"], &input);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().count() <= 6, "Got: {}", stdout);
+    assert!(stdout.contains("(truncated)"));
+}
+
+#[test]
+fn test_list_languages_without_file() {
+    let output = run_with_stdin(&["--list-languages"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let names: Vec<&str> = stdout.lines().map(|l| l.split(' ').next().unwrap()).collect();
+    assert!(names.contains(&"rust"), "Got: {}", stdout);
+    assert!(names.contains(&"json"), "Got: {}", stdout);
+}
+
+#[test]
+fn test_fold_outputs_plain_text_within_width() {
+    let output = run_with_stdin(&["--fold", "20", "-"], "**bold** text\n\n```\nfn a_very_long_function_name() {}\n```\n");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains('\x1b'), "{:?}", stdout);
+    assert!(stdout.lines().all(|l| l.chars().count() <= 20), "{:?}", stdout);
+}
+
+#[test]
+fn test_verbose_logs_event_count_to_stderr() {
+    let output = run_with_stdin(&["-v", "-"], "# Title\n\nHello.\n");
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("parsed 6 events"), "{}", stderr);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("parsed"));
+}
+
+#[test]
+fn test_check_reports_overflowing_line() {
+    let input = format!("Fine.\n\n{}\n", "x".repeat(200));
+    let output = run_with_stdin(&["--check", "-w", "80", "-"], &input);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("200 columns wide"));
+
+    let output = run_with_stdin(&["--check", "-w", "80", "-"], "Fine.\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_eval_renders_argument() {
+    let output = run_with_stdin(&["-e", "**bold**"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("bold") && !stdout.contains("**"), "{:?}", stdout);
+}
+
+#[test]
+fn test_dump_trims_trailing_spaces_but_keeps_resets() {
+    let input = "| Name | Description |\n|---|---|\n| a | **b** |\n";
+    let output = run_with_stdin(&["--table-style", "simple", "-"], input);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.lines().all(|l| !l.ends_with(' ')), "{:?}", stdout);
+    assert!(stdout.contains("\x1b[0m"), "{:?}", stdout);
+
+    let output = run_with_stdin(&["--table-style", "simple", "--keep-trailing", "-"], input);
+    assert!(String::from_utf8_lossy(&output.stdout).lines().any(|l| l.ends_with(' ')));
+}
+
+#[test]
+fn test_teletype_output_is_plain_ascii() {
+    let input = "# Title\n\n- **bold** — “quotes” → é\n- [x] done\n\n> quote\n\n```rust\nlet s = \"\x1b[31m\";\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n";
+    for args in [&["--teletype", "--symbols", "-"][..], &["--teletype", "--head", "3", "-"]] {
+        let output = run_with_stdin(args, input);
+        assert_eq!(output.status.code(), Some(0));
+        assert!(output.stdout.iter().all(|&b| b < 128), "{}", String::from_utf8_lossy(&output.stdout));
+        assert!(!output.stdout.contains(&0x1b));
+    }
+}
+
+#[test]
+fn test_code_plain_copy_prints_source_only() {
+    let input = "# Title\n\n```sh\n\techo \"hi\"\n    ls\n```\n\ntext\n\n```\nsecond\n```\n";
+    let output = run_with_stdin(&["--code-plain-copy", "-"], input);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "\techo \"hi\"\n    ls\n\nsecond\n");
+}
+
+#[test]
+fn test_explain_width_reports_flag() {
+    let output = run_with_stdin(&["--explain-width", "-w", "50"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "50 (flag)\n");
+}
+
+#[test]
+fn test_raw_prints_source_lines() {
+    let input = "# Title\n\n- *item*\n\n```\ncode\n```\n";
+    let output = run_with_stdin(&["--raw", "--theme", "none", "-"], input);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), input);
+}
+
+#[test]
+fn test_pager_only_passes_lines_through() {
+    let input = "\x1b[31mred\x1b[0m  \n# not rendered\n";
+    let output = run_with_stdin(&["--pager-only", "-"], input);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), input);
+}
+
+#[test]
+fn test_latin1_file_with_encoding() {
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/latin1.md");
+    let output = run_with_stdin(&["--encoding", "latin1", "--theme", "none", fixture], "");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Café"));
+    assert!(stdout.contains("Naïve crème brûlée."));
+}